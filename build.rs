@@ -0,0 +1,70 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // `@font-face` fonts are handed to Pango through its fontconfig backend, see
+    // src/style/font_face.rs
+    let family = std::env::var("CARGO_CFG_TARGET_FAMILY").unwrap_or_default();
+    let vendor = std::env::var("CARGO_CFG_TARGET_VENDOR").unwrap_or_default();
+    if family.split(',').any(|family| family == "unix") && vendor != "apple" {
+        let probed = pkg_config::Config::new()
+            .atleast_version("1.50")
+            .probe("pangoft2");
+        match probed {
+            Ok(library) => check_set_config(&library.include_paths),
+            Err(error) => {
+                eprintln!(
+                    "css-image needs pangoft2 1.50 or newer, Pango's fontconfig backend. It comes \
+                     with Pango's development files, e.g. libpango1.0-dev or pango-devel\n\n{error}"
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// `pango_fc_font_map_set_config` is declared by hand in src/style/font_face.rs, compile the same
+// prototype against Pango's header to catch a mismatch
+fn check_set_config(include_paths: &[std::path::PathBuf]) {
+    let header = "pango/pangofc-fontmap.h";
+    if !include_paths.iter().any(|path| path.join(header).exists()) {
+        println!("cargo:warning={header} was not found, pango_fc_font_map_set_config is unchecked");
+        return;
+    }
+
+    let out_dir = std::path::PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    let source = out_dir.join("set_config.c");
+    let written = std::fs::write(
+        &source,
+        format!(
+            "#include <{header}>\n\
+             void (*const check)(PangoFcFontMap *, FcConfig *) = pango_fc_font_map_set_config;\n"
+        ),
+    );
+    if let Err(error) = written {
+        println!("cargo:warning=Could not check pango_fc_font_map_set_config: {error}");
+        return;
+    }
+
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let mut command = std::process::Command::new(&compiler);
+    command.args(["-fsyntax-only", "-Werror"]);
+    for path in include_paths {
+        command.arg("-I").arg(path);
+    }
+    match command.arg(&source).output() {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            eprintln!(
+                "pango_fc_font_map_set_config in src/style/font_face.rs doesn't match {header}\n\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            std::process::exit(1);
+        }
+        Err(error) => {
+            println!(
+                "cargo:warning=Could not run {compiler} to check pango_fc_font_map_set_config: \
+                 {error}"
+            );
+        }
+    }
+}
@@ -26,6 +26,10 @@
           rustfmt
           clippy
         ];
+        buildInputs = with pkgs; [
+          pango
+          fontconfig
+        ];
       };
     });
   };
@@ -0,0 +1,238 @@
+use crate::parser::{self, Rule};
+use crate::selector::{Selector, Specificity};
+use crate::style::{
+    split_background, split_border, split_radius, split_text_decoration, split_text_stroke,
+    BACKGROUND_LONGHANDS, CORNERS, TEXT_DECORATION_LONGHANDS,
+};
+use std::collections::HashMap;
+
+pub(crate) const SIDES: [&str; 4] = ["top", "right", "bottom", "left"];
+
+// Maps the 1 to 4 values of a box shorthand onto the top, right, bottom and left sides
+pub(crate) fn box_sides<T: Copy>(values: &[T]) -> Option<[T; 4]> {
+    match *values {
+        [all] => Some([all; 4]),
+        [vertical, horizontal] => Some([vertical, horizontal, vertical, horizontal]),
+        [top, horizontal, bottom] => Some([top, horizontal, bottom, horizontal]),
+        [top, right, bottom, left] => Some([top, right, bottom, left]),
+        _ => None,
+    }
+}
+
+// The longhands a shorthand sets, in the order `split` returns their values
+fn longhands(name: &str) -> Option<Vec<String>> {
+    let longhands = match name {
+        "margin" | "padding" => SIDES.iter().map(|side| format!("{name}-{side}")).collect(),
+        "border-width" | "border-style" | "border-color" => {
+            let property = &name["border-".len()..];
+            SIDES
+                .iter()
+                .map(|side| format!("border-{side}-{property}"))
+                .collect()
+        }
+        "border-radius" => CORNERS
+            .iter()
+            .map(|corner| format!("border-{corner}-radius"))
+            .collect(),
+        "border" | "border-top" | "border-right" | "border-bottom" | "border-left" => {
+            let sides = match name.strip_prefix("border-") {
+                Some(side) => vec![side],
+                None => SIDES.to_vec(),
+            };
+            sides
+                .iter()
+                .flat_map(|side| {
+                    ["width", "style", "color"]
+                        .iter()
+                        .map(move |property| format!("border-{side}-{property}"))
+                })
+                .collect()
+        }
+        "background" => BACKGROUND_LONGHANDS
+            .iter()
+            .map(|(longhand, _)| longhand.to_string())
+            .collect(),
+        "text-decoration" => TEXT_DECORATION_LONGHANDS
+            .iter()
+            .map(|(longhand, _)| longhand.to_string())
+            .collect(),
+        "-webkit-text-stroke" => vec![
+            "-webkit-text-stroke-width".to_string(),
+            "-webkit-text-stroke-color".to_string(),
+        ],
+        _ => return None,
+    };
+    Some(longhands)
+}
+
+// Splits the value of a shorthand into the values of its longhands
+fn split(name: &str, value: &str) -> Option<Vec<String>> {
+    let values = match name {
+        "margin" | "padding" | "border-width" | "border-style" | "border-color" => {
+            box_sides(&parser::split_values(value))?
+                .map(str::to_string)
+                .to_vec()
+        }
+        "border-radius" => split_radius(value)?.to_vec(),
+        // Every side gets the same width, style and color
+        "border" => {
+            let values = split_border(value)?;
+            SIDES.iter().flat_map(|_| values.clone()).collect()
+        }
+        "border-top" | "border-right" | "border-bottom" | "border-left" => {
+            split_border(value)?.to_vec()
+        }
+        "background" => split_background(value)?.to_vec(),
+        "text-decoration" => split_text_decoration(value)?.to_vec(),
+        "-webkit-text-stroke" => split_text_stroke(value)?.to_vec(),
+        _ => return None,
+    };
+    Some(values)
+}
+
+// Shorthands are expanded so that each longhand cascades on its own
+fn expand(name: &str, value: &str) -> Vec<(String, String)> {
+    let Some(longhands) = longhands(name) else {
+        return vec![(name.to_string(), value.to_string())];
+    };
+    match split(name, value) {
+        Some(values) => longhands.into_iter().zip(values).collect(),
+        None => Vec::new(),
+    }
+}
+
+// The cascaded values of an element's properties
+pub(crate) struct Cascaded {
+    pub properties: HashMap<Box<str>, String>,
+    // https://www.w3.org/TR/css-variables-1/#variables-in-shorthands
+    // Shorthands using `var()` can only be split once the variables are substituted, until
+    // then each of their longhands holds the whole value. Maps those longhands to the shorthand
+    pending: HashMap<Box<str>, String>,
+}
+
+impl Cascaded {
+    // Splits the substituted values of shorthands using `var()`. The longhands of the ones that
+    // turned out invalid are dropped, like properties using `var()` that are invalid
+    pub(crate) fn expand_pending(&mut self) {
+        for (longhand, shorthand) in &self.pending {
+            let Some(value) = self.properties.remove(longhand) else {
+                continue;
+            };
+            let value = expand(shorthand, &value)
+                .into_iter()
+                .find(|(name, _)| **name == **longhand);
+            if let Some((_, value)) = value {
+                self.properties.insert(longhand.clone(), value);
+            }
+        }
+        self.pending.clear();
+    }
+}
+
+// Importance, specificity and source order of a declaration
+type Priority = (bool, Specificity, usize);
+
+// https://www.w3.org/TR/css-cascade-4/#cascade-sort
+// Declarations are sorted by importance, then specificity, then source order
+pub(crate) fn cascade(rules: &[Rule], element: &Selector) -> Cascaded {
+    let mut winners: HashMap<String, (Priority, String, Option<&str>)> = HashMap::new();
+    let mut order = 0;
+
+    for rule in rules {
+        let specificity = rule
+            .selectors
+            .iter()
+            .map(|selector| Selector::parse(selector))
+            .filter(|selector| selector.matches(element))
+            .map(|selector| selector.specificity())
+            .max();
+
+        let Some(specificity) = specificity else {
+            order += rule.declarations.len();
+            continue;
+        };
+
+        for declaration in &rule.declarations {
+            let priority = (declaration.important, specificity, order);
+            order += 1;
+
+            let (name, value) = (declaration.name.as_str(), &declaration.value);
+            let expanded = match longhands(name) {
+                Some(longhands) if value.contains("var(") => longhands
+                    .into_iter()
+                    .map(|longhand| (longhand, value.clone(), Some(name)))
+                    .collect(),
+                _ => expand(name, value)
+                    .into_iter()
+                    .map(|(longhand, value)| (longhand, value, None))
+                    .collect::<Vec<_>>(),
+            };
+
+            for (name, value, shorthand) in expanded {
+                match winners.get(&name) {
+                    Some((winner, ..)) if *winner > priority => {}
+                    _ => {
+                        winners.insert(name, (priority, value, shorthand));
+                    }
+                }
+            }
+        }
+    }
+
+    let pending = winners
+        .iter()
+        .filter_map(|(name, (_, _, shorthand))| {
+            Some((name.as_str().into(), (*shorthand)?.to_string()))
+        })
+        .collect();
+    let properties = winners
+        .into_iter()
+        .map(|(name, (_, value, _))| (name.into(), value))
+        .collect();
+
+    Cascaded {
+        properties,
+        pending,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_stylesheet;
+
+    #[test]
+    fn test_cascade() {
+        let stylesheet = parse_stylesheet(
+            r#"
+            #battery.icon { color: red; width: 1px; margin: 1px 2px; }
+            .icon { color: blue; width: 2px !important; height: 2px; margin-top: 5px; }
+            * { height: 3px; content: "a"; }
+            .icon { height: 4px; margin-left: 6px; }
+            #battery { border: 1px solid red; border-left-color: blue; border-width: 2px 3px; }
+            .icon { border-radius: 1px 2px / 3px; border-bottom-left-radius: 50%; }
+            "#,
+        );
+
+        let properties = cascade(&stylesheet.rules, &Selector::parse("#battery.icon")).properties;
+        assert_eq!(properties["color"], "red");
+        assert_eq!(properties["width"], "2px");
+        assert_eq!(properties["height"], "4px");
+        assert_eq!(properties["content"], "\"a\"");
+        assert_eq!(properties["margin-top"], "1px");
+        assert_eq!(properties["margin-right"], "2px");
+        assert_eq!(properties["margin-left"], "2px");
+        assert_eq!(properties["border-top-width"], "2px");
+        assert_eq!(properties["border-left-width"], "3px");
+        assert_eq!(properties["border-bottom-style"], "solid");
+        assert_eq!(properties["border-left-color"], "blue");
+        assert_eq!(properties["border-right-color"], "red");
+        assert_eq!(properties["border-top-left-radius"], "1px 3px");
+        assert_eq!(properties["border-top-right-radius"], "2px 3px");
+        assert_eq!(properties["border-bottom-left-radius"], "50%");
+
+        let properties = cascade(&stylesheet.rules, &Selector::parse("#clock")).properties;
+        assert_eq!(properties.len(), 2);
+        assert_eq!(properties["height"], "3px");
+    }
+}
@@ -0,0 +1,138 @@
+use std::{fmt, ops::Range};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// Lines and columns are 1-based, columns are counted in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub selector: Option<String>,
+    pub property: Option<String>,
+    pub value: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}: {severity}: {}",
+            self.span.start.line, self.span.start.column, self.message
+        )?;
+        match (&self.property, &self.value) {
+            (Some(property), Some(value)) => write!(f, " (`{property}: {value}`)"),
+            (Some(property), None) => write!(f, " (`{property}`)"),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub(crate) struct LineIndex {
+    source_len: usize,
+    line_starts: Vec<usize>,
+    chars: Vec<(usize, usize)>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        // Byte offset to character index, only recorded where they diverge
+        let chars = source
+            .char_indices()
+            .enumerate()
+            .filter(|(index, (offset, _))| index != offset)
+            .map(|(index, (offset, _))| (offset, index))
+            .collect();
+
+        Self {
+            source_len: source.len(),
+            line_starts,
+            chars,
+        }
+    }
+
+    fn char_index(&self, offset: usize) -> usize {
+        match self.chars.binary_search_by_key(&offset, |&(o, _)| o) {
+            Ok(i) => self.chars[i].1,
+            Err(0) => offset,
+            Err(i) => self.chars[i - 1].1 + (offset - self.chars[i - 1].0),
+        }
+    }
+
+    pub fn location(&self, offset: usize) -> Location {
+        let offset = offset.min(self.source_len);
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let column = self.char_index(offset) - self.char_index(self.line_starts[line - 1]);
+
+        Location {
+            line,
+            column: column + 1,
+        }
+    }
+
+    pub fn span(&self, range: Range<usize>) -> Span {
+        Span {
+            start: self.location(range.start),
+            end: self.location(range.end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locations() {
+        let index = LineIndex::new("a {\n  ö: b;\n}");
+        assert_eq!(index.location(0), Location { line: 1, column: 1 });
+        assert_eq!(index.location(4), Location { line: 2, column: 1 });
+        assert_eq!(index.location(8), Location { line: 2, column: 4 });
+        assert_eq!(
+            index.span(13..14),
+            Span {
+                start: Location { line: 3, column: 1 },
+                end: Location { line: 3, column: 2 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            span: Span::default(),
+            selector: Some("body".into()),
+            property: Some("width".into()),
+            value: Some("100".into()),
+            message: "Expected a length in px".into(),
+        };
+        assert_eq!(
+            diagnostic.to_string(),
+            "0:0: error: Expected a length in px (`width: 100`)"
+        );
+    }
+}
@@ -1,42 +1,87 @@
 use std::{error::Error, fmt};
 
-#[allow(clippy::enum_variant_names)]
-#[derive(Debug, Clone)]
-pub enum CssError<'a> {
-    SizeError(&'a str),
-    ContentError(&'a str),
-    ParseError,
-    FontError(&'a str),
+#[derive(Debug)]
+pub enum CssError {
+    SurfaceCreation {
+        selector: String,
+        source: cairo::Error,
+    },
+    ContextCreation {
+        selector: String,
+        source: cairo::Error,
+    },
+    Paint {
+        selector: String,
+        source: cairo::Error,
+    },
+    TextRendering {
+        selector: String,
+        source: cairo::Error,
+    },
+    PngEncoding {
+        selector: String,
+        source: cairo::IoError,
+    },
+    InvalidTextAlign {
+        selector: String,
+        value: String,
+    },
+    UnknownUnit {
+        selector: String,
+        unit: String,
+    },
 }
 
-impl fmt::Display for CssError<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl CssError {
+    pub fn selector(&self) -> &str {
         match self {
-            CssError::SizeError(message) => write!(f, "SizeError: {message}"),
-            CssError::ContentError(message) => write!(f, "ContentError: {message}"),
-            CssError::ParseError => write!(f, "ParseError: Failed to parse CSS"),
-            CssError::FontError(message) => write!(f, "FontError: {message}"),
+            CssError::SurfaceCreation { selector, .. }
+            | CssError::ContextCreation { selector, .. }
+            | CssError::Paint { selector, .. }
+            | CssError::TextRendering { selector, .. }
+            | CssError::PngEncoding { selector, .. }
+            | CssError::InvalidTextAlign { selector, .. }
+            | CssError::UnknownUnit { selector, .. } => selector,
         }
     }
 }
 
-impl Error for CssError<'_> {}
-
-impl From<std::num::ParseFloatError> for CssError<'_> {
-    fn from(_: std::num::ParseFloatError) -> Self {
-        CssError::SizeError("ParseFloatError")
-    }
-}
-
-impl From<std::num::ParseIntError> for CssError<'_> {
-    fn from(_: std::num::ParseIntError) -> Self {
-        CssError::SizeError("ParseIntError")
+impl fmt::Display for CssError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CssError::SurfaceCreation { selector, .. } => {
+                write!(f, "Failed to create cairo surface for `{selector}`")
+            }
+            CssError::ContextCreation { selector, .. } => {
+                write!(f, "Failed to create cairo context for `{selector}`")
+            }
+            CssError::Paint { selector, .. } => write!(f, "Failed to paint `{selector}`"),
+            CssError::TextRendering { selector, .. } => {
+                write!(f, "Failed to draw the content of `{selector}`")
+            }
+            CssError::PngEncoding { selector, .. } => {
+                write!(f, "Failed to write `{selector}` as PNG")
+            }
+            CssError::InvalidTextAlign { selector, value } => {
+                write!(f, "Invalid text-align `{value}` in `{selector}`")
+            }
+            CssError::UnknownUnit { selector, unit } => {
+                write!(f, "Unknown unit `{unit}` in `{selector}`")
+            }
+        }
     }
 }
 
-impl From<&str> for CssError<'_> {
-    fn from(_: &str) -> Self {
-        CssError::ContentError("&str")
+impl Error for CssError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CssError::SurfaceCreation { source, .. }
+            | CssError::ContextCreation { source, .. }
+            | CssError::Paint { source, .. }
+            | CssError::TextRendering { source, .. } => Some(source),
+            CssError::PngEncoding { source, .. } => Some(source),
+            CssError::InvalidTextAlign { .. } | CssError::UnknownUnit { .. } => None,
+        }
     }
 }
 
@@ -46,34 +91,60 @@ mod tests {
 
     #[test]
     fn test_css_error() {
-        let error = CssError::SizeError("Test");
-        assert_eq!(error.to_string(), "SizeError: Test");
-
-        let error = CssError::ContentError("Test");
-        assert_eq!(error.to_string(), "ContentError: Test");
-
-        let error = CssError::ParseError;
-        assert_eq!(error.to_string(), "ParseError: Failed to parse CSS");
-
-        let error = CssError::FontError("Test");
-        assert_eq!(error.to_string(), "FontError: Test");
-
-        let error = CssError::SizeError("Test");
-        assert_eq!(error.to_string(), "SizeError: Test");
-
-        let error = CssError::SizeError("Test");
-        assert_eq!(error.to_string(), "SizeError: Test");
-
-        let error = "";
-        let error = CssError::from(error);
-        assert_eq!(error.to_string(), "ContentError: &str");
-
-        let error = "".parse::<f32>().unwrap_err();
-        let error = CssError::from(error);
-        assert_eq!(error.to_string(), "SizeError: ParseFloatError");
-
-        let error = "".parse::<i32>().unwrap_err();
-        let error = CssError::from(error);
-        assert_eq!(error.to_string(), "SizeError: ParseIntError");
+        let error = CssError::SurfaceCreation {
+            selector: "body".into(),
+            source: cairo::Error::InvalidSize,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Failed to create cairo surface for `body`"
+        );
+        assert_eq!(error.selector(), "body");
+        assert_eq!(
+            error.source().map(|e| e.to_string()),
+            Some(cairo::Error::InvalidSize.to_string())
+        );
+
+        let error = CssError::ContextCreation {
+            selector: "body".into(),
+            source: cairo::Error::NoMemory,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Failed to create cairo context for `body`"
+        );
+
+        let error = CssError::Paint {
+            selector: "body".into(),
+            source: cairo::Error::NoMemory,
+        };
+        assert_eq!(error.to_string(), "Failed to paint `body`");
+
+        let error = CssError::TextRendering {
+            selector: "body".into(),
+            source: cairo::Error::InvalidString,
+        };
+        assert_eq!(error.to_string(), "Failed to draw the content of `body`");
+
+        let error = CssError::PngEncoding {
+            selector: "body".into(),
+            source: cairo::IoError::Cairo(cairo::Error::WriteError),
+        };
+        assert_eq!(error.to_string(), "Failed to write `body` as PNG");
+        assert!(error.source().is_some());
+
+        let error = CssError::InvalidTextAlign {
+            selector: "body".into(),
+            value: "justify".into(),
+        };
+        assert_eq!(error.to_string(), "Invalid text-align `justify` in `body`");
+        assert!(error.source().is_none());
+
+        let error = CssError::UnknownUnit {
+            selector: "body".into(),
+            unit: "foo".into(),
+        };
+        assert_eq!(error.to_string(), "Unknown unit `foo` in `body`");
+        assert!(error.source().is_none());
     }
 }
@@ -1,229 +1,542 @@
+mod cascade;
+pub mod diagnostics;
 mod error;
+mod options;
+mod parser;
+mod selector;
 pub mod style;
+mod variables;
+
+pub use error::CssError;
+pub use options::{RenderOptions, Resolver};
+pub use parser::Stylesheet;
+pub use selector::Element;
 
 use cairo::{Context, ImageSurface};
-use error::CssError;
-use lazy_static::lazy_static;
+use diagnostics::Diagnostic;
 use rayon::prelude::*;
-use regex::Regex;
+use selector::Selector;
 use std::{collections::HashMap, ops::Deref};
-use style::{Parseable, Style};
+use style::{
+    inset_rectangle, rounded_rectangle, BackgroundClip, BackgroundOrigin, FontFace, Parseable,
+    Style, TextLayout,
+};
 
-lazy_static! {
-    static ref RE: Regex =
-        Regex::new(r"(?P<selector>\S+)\s*\{\s*(?P<properties>[^}]+)\s*\}").unwrap();
-    static ref PROPERTY_RE: Regex =
-        Regex::new(r"(?P<property>[\w-]+):\s*(?P<value>[^;]+);").unwrap();
+pub fn parse<T>(css: T) -> Result<Vec<Style>, CssError>
+where
+    T: AsRef<str>,
+{
+    let stylesheet = Stylesheet::parse(css.as_ref());
+    stylesheet.check_units(None)?;
+
+    Ok(compute_styles(&stylesheet, &RenderOptions::default()))
 }
 
-pub fn parse<T>(css: T) -> Result<Vec<Style>, CssError<'static>>
+pub fn parse_with_diagnostics<T>(css: T) -> Result<(Vec<Style>, Vec<Diagnostic>), CssError>
 where
     T: AsRef<str>,
 {
-    let split = css
-        .as_ref()
-        .split_inclusive('}')
-        .filter_map(|selector| {
-            let selector = selector.trim();
-            if selector.is_empty() {
-                return None;
-            }
-            Some(selector)
-        })
-        .collect::<Vec<&str>>();
-
-    let all_selector = split.iter().find_map(|s| {
-        let mut properties: HashMap<Box<str>, String> = HashMap::new();
-
-        for cap in RE.captures_iter(s) {
-            if &cap["selector"] == "*" {
-                for property_cap in PROPERTY_RE.captures_iter(&cap["properties"]) {
-                    properties.insert(
-                        property_cap["property"].into(),
-                        property_cap["value"].to_string(),
-                    );
-                }
-                return Some(properties);
-            }
-        }
-        None
-    });
+    let stylesheet = Stylesheet::parse(css.as_ref());
+    let styles = compute_styles(&stylesheet, &RenderOptions::default());
 
-    Ok(split
-        .par_iter()
-        .filter_map(|s| {
-            let mut properties = HashMap::with_capacity(split.len() - 1);
-
-            if let Some(cap) = RE.captures_iter(s).next() {
-                let selector = cap["selector"].to_string();
-                PROPERTY_RE
-                    .captures_iter(&cap["properties"])
-                    .for_each(|property_cap| {
-                        properties.insert(
-                            property_cap["property"].into(),
-                            property_cap["value"].to_string(),
-                        );
-                    });
-                return Some((selector, properties));
-            }
+    Ok((styles, stylesheet.diagnostics))
+}
 
-            None
+pub(crate) fn compute_styles(stylesheet: &Stylesheet, options: &RenderOptions) -> Vec<Style> {
+    let faces = stylesheet.font_faces();
+    stylesheet
+        .selectors()
+        .par_iter()
+        .map(|&selector| {
+            let element = Selector::parse(selector);
+            compute_style(stylesheet, &element, selector, options, &faces)
         })
-        .map(|(selector, properties)| Style::new(selector, &properties, all_selector.as_ref()))
-        .collect::<Vec<Style>>())
+        .collect()
+}
+
+fn compute_style(
+    stylesheet: &Stylesheet,
+    element: &Selector,
+    name: &str,
+    options: &RenderOptions,
+    faces: &[FontFace],
+) -> Style {
+    let mut cascaded = cascade::cascade(&stylesheet.rules, element);
+    variables::substitute(&mut cascaded.properties, &options.variables);
+    cascaded.expand_pending();
+    Style::new(name.to_string(), &cascaded.properties, options, faces)
+}
+
+pub fn render<T>(css: T) -> Result<HashMap<String, Vec<u8>>, CssError>
+where
+    T: Parseable,
+{
+    render_with_options(css, &RenderOptions::default())
+}
+
+pub fn render_with_options<T>(
+    css: T,
+    options: &RenderOptions,
+) -> Result<HashMap<String, Vec<u8>>, CssError>
+where
+    T: Parseable,
+{
+    let rendered = render_with_metadata(css, options)?;
+
+    Ok(rendered
+        .into_iter()
+        .map(|(selector, rendered)| (selector, rendered.png))
+        .collect())
+}
+
+// An image along with what went into rendering it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub png: Vec<u8>,
+    // The families the content was drawn with, in order of first use. A family the style asks
+    // for that is missing here wasn't found
+    pub font_families: Vec<String>,
 }
 
-pub fn render<T>(css: T) -> Result<HashMap<String, Vec<u8>>, CssError<'static>>
+pub fn render_with_metadata<T>(
+    css: T,
+    options: &RenderOptions,
+) -> Result<HashMap<String, Rendered>, CssError>
 where
     T: Parseable,
 {
-    let mut styles = css.parse()?;
+    let mut styles = css.parse_with_options(options)?;
 
     styles
         .par_iter_mut()
-        .map(|style| {
-            let name = &style.selector;
+        .map(|style| Ok((style.selector.clone(), render_style(style, options)?)))
+        .collect::<Result<HashMap<_, _>, CssError>>()
+}
 
-            let mut width = style.width;
-            let mut height = style.height;
-            let mut position = 0;
+pub fn render_element(
+    stylesheet: &Stylesheet,
+    element: &Element,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, CssError> {
+    let name = element.to_string();
+    let faces = stylesheet.font_faces();
+    let element = Selector::from(element);
+    stylesheet.check_units(Some(&element))?;
+    let mut style = compute_style(stylesheet, &element, &name, options, &faces);
+    render_style(&mut style, options).map(|rendered| rendered.png)
+}
 
-            let mut text_width = 0;
+// Draws the outer or the inset shadows, the last one first. Outer ones only show outside of
+// the border box and inset ones inside of the padding box
+fn draw_shadows(
+    context: &Context,
+    style: &Style,
+    border_box: (f64, f64, f64, f64),
+    inset: bool,
+) -> Result<(), cairo::Error> {
+    let (x, y, width, height, radii) = match inset {
+        true => style
+            .border
+            .inset(border_box.0, border_box.1, border_box.2, border_box.3, 1.),
+        false => (
+            border_box.0,
+            border_box.1,
+            border_box.2,
+            border_box.3,
+            style.border.radii(border_box.2, border_box.3),
+        ),
+    };
 
-            if let Some(content) = &style.content {
-                if content.is_empty() {
-                    style.content = None;
-                }
+    for shadow in style.box_shadow.iter().rev().filter(|s| s.inset == inset) {
+        context.save()?;
+        if !inset {
+            let (x1, y1, x2, y2) = context.clip_extents()?;
+            context.set_fill_rule(cairo::FillRule::EvenOdd);
+            context.rectangle(x1, y1, x2 - x1, y2 - y1);
+        }
+        rounded_rectangle(context, x, y, width, height, radii);
+        context.clip();
+        shadow.draw(context, x, y, width, height, radii)?;
+        context.restore()?;
+    }
+
+    Ok(())
+}
+
+// Paints the background layers from the bottom up, each clipped to the box its
+// `background-clip` asks for. Layers clipped to the text are only painted when `text` is set,
+// with the clipping up to the caller, and the other layers only when it's not
+fn draw_background(
+    context: &Context,
+    style: &Style,
+    border_box: (f64, f64, f64, f64),
+    options: &RenderOptions,
+    text: bool,
+) -> Result<(), cairo::Error> {
+    let (x, y, width, height) = border_box;
+    let padding_box = style.border.inset(x, y, width, height, 1.);
+    let content_box = inset_rectangle(padding_box, style.padding.map(f64::from));
+    let border_box = (x, y, width, height, style.border.radii(width, height));
+
+    for layer in style.background.iter().rev() {
+        if (layer.clip == BackgroundClip::Text) != text {
+            continue;
+        }
+        let clip = match layer.clip {
+            BackgroundClip::BorderBox | BackgroundClip::Text => border_box,
+            BackgroundClip::PaddingBox => padding_box,
+            BackgroundClip::ContentBox => content_box,
+        };
+        let origin = match layer.origin {
+            BackgroundOrigin::BorderBox => border_box,
+            BackgroundOrigin::PaddingBox => padding_box,
+            BackgroundOrigin::ContentBox => content_box,
+        };
+
+        context.save()?;
+        rounded_rectangle(context, clip.0, clip.1, clip.2, clip.3, clip.4);
+        context.clip();
+        layer.draw(
+            context,
+            options,
+            (origin.0, origin.1, origin.2, origin.3),
+            (clip.0, clip.1, clip.2, clip.3),
+        )?;
+        context.restore()?;
+    }
+
+    Ok(())
+}
+
+// Draws the lines at their origins, with underlines and overlines under the glyphs, the stroke
+// over them and line-throughs on top of it all
+fn draw_text(
+    context: &Context,
+    style: &Style,
+    text: &TextLayout,
+    origins: &[(f64, f64)],
+) -> Result<(), cairo::Error> {
+    let font = &style.font;
+    let decoration = &font.text_decoration;
+    let metrics = text.decoration;
+    let lines = || text.lines.iter().zip(origins);
+
+    // Underlines and overlines have their tops where the font puts them, whatever the thickness
+    let thickness = decoration.thickness.unwrap_or(metrics.underline_thickness);
+    for (line, origin) in lines() {
+        let x = origin.0 + line.left;
+        if decoration.underline {
+            let y = origin.1 + metrics.underline + thickness / 2.;
+            decoration.draw(context, x, y, line.width, thickness)?;
+        }
+        if decoration.overline {
+            let y = origin.1 - metrics.ascent + thickness / 2.;
+            decoration.draw(context, x, y, line.width, thickness)?;
+        }
+    }
+
+    context.set_source_rgba(font.color[0], font.color[1], font.color[2], font.color[3]);
+    for (line, origin) in lines() {
+        context.move_to(origin.0, origin.1);
+        pangocairo::functions::show_layout_line(context, &line.line);
+    }
+
+    // The stroke is centered on the outlines of the glyphs
+    let stroke = &font.text_stroke;
+    if stroke.width > 0. {
+        for (line, origin) in lines() {
+            context.move_to(origin.0, origin.1);
+            pangocairo::functions::layout_line_path(context, &line.line);
+        }
+        context.set_source_rgba(
+            stroke.color[0],
+            stroke.color[1],
+            stroke.color[2],
+            stroke.color[3],
+        );
+        context.set_line_width(stroke.width);
+        context.stroke()?;
+    }
+
+    // Line-throughs stay centered where the font puts them
+    if decoration.line_through {
+        let thickness = decoration
+            .thickness
+            .unwrap_or(metrics.line_through_thickness);
+        for (line, origin) in lines() {
+            let y = origin.1 + metrics.line_through + metrics.line_through_thickness / 2.;
+            decoration.draw(context, origin.0 + line.left, y, line.width, thickness)?;
+        }
+    }
+
+    context.status()
+}
+
+// The area the lines paint in, with room for the stroke and for wavy or double decorations
+fn text_bounds(text: &TextLayout, origins: &[(f64, f64)], style: &Style) -> (f64, f64, f64, f64) {
+    let font = &style.font;
+    let thickness = font.text_decoration.thickness.unwrap_or(
+        text.decoration
+            .underline_thickness
+            .max(text.decoration.line_through_thickness),
+    );
+    let pad = font.text_stroke.width / 2. + thickness * 2. + 1.;
+
+    // The decorations run along each line, from the overline down to the underline
+    let (mut x1, mut y1, mut x2, mut y2) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for (line, origin) in text.lines.iter().zip(origins) {
+        let ink = line.ink;
+        let decorations = (
+            line.left,
+            -text.decoration.ascent,
+            line.width,
+            text.decoration.ascent + text.decoration.underline + thickness,
+        );
+        for (x, y, width, height) in [ink, decorations] {
+            x1 = x1.min(origin.0 + x);
+            y1 = y1.min(origin.1 + y);
+            x2 = x2.max(origin.0 + x + width);
+            y2 = y2.max(origin.1 + y + height);
+        }
+    }
+
+    match x1 <= x2 {
+        true => (x1 - pad, y1 - pad, x2 - x1 + pad * 2., y2 - y1 + pad * 2.),
+        false => (0., 0., 0., 0.),
+    }
+}
+
+fn render_style(style: &mut Style, options: &RenderOptions) -> Result<Rendered, CssError> {
+    let name = &style.selector;
+
+    let mut width = style.width;
+    let mut height = style.height;
+    let mut text = TextLayout::default();
+
+    if let Some(content) = &style.content {
+        if content.is_empty() {
+            style.content = None;
+        }
+    }
+    if let Some(content) = &style.content {
+        let surface = ImageSurface::create(cairo::Format::ARgb32, 0, 0).map_err(|source| {
+            CssError::SurfaceCreation {
+                selector: name.clone(),
+                source,
             }
-            if let Some(content) = &style.content {
-                let surface = ImageSurface::create(cairo::Format::ARgb32, 0, 0)
-                    .map_err(|_| CssError::ContentError("Failed to create cairo surface"))?;
-                let context = Context::new(&surface)
-                    .map_err(|_| CssError::ContentError("Failed to create cairo context"))?;
-                let font = &style.font;
-
-                context.select_font_face(font.family.deref(), font.style, font.weight);
-                context.set_font_size(font.size);
-                let extents = context
-                    .text_extents(content.deref())
-                    .map_err(|_| CssError::ContentError(""))?;
-
-                if width.is_none() {
-                    width = Some(extents.width() as i32);
-                }
-                if height.is_none() {
-                    height = Some(extents.height() as i32);
-                }
-                text_width = extents.width() as i32;
-                position = extents.y_bearing().abs() as i32;
+        })?;
+        let context = Context::new(&surface).map_err(|source| CssError::ContextCreation {
+            selector: name.clone(),
+            source,
+        })?;
+        // Lines wrap at the width, if there is one
+        text = style::layout_text(
+            &context,
+            content,
+            &style.font,
+            width.map(f64::from),
+            options,
+        );
+
+        if width.is_none() {
+            width = Some(text.width().ceil() as i32);
+        }
+        if height.is_none() {
+            height = Some(text.height.ceil() as i32);
+        }
+    }
+
+    let margin = style.margin;
+    let padding = style.padding;
+    let border = style.border.width;
+
+    let width = width.unwrap_or(5);
+    let height = height.unwrap_or(5);
+
+    // The border box sits inside of the margins, with the content inside of its border and padding
+    let border_box = (
+        margin[3] as f64,
+        margin[0] as f64,
+        (width + padding[1] + padding[3] + border[1] + border[3]) as f64,
+        (height + padding[0] + padding[2] + border[0] + border[2]) as f64,
+    );
+    let content_x = margin[3] + border[3] + padding[3];
+    let content_y = margin[0] + border[0] + padding[0];
+
+    // Outer shadows reaching past the margins grow the image, unless they are clipped to them
+    let mut overflow = [0; 4];
+    if !options.clip_shadows {
+        for shadow in &style.box_shadow {
+            for ((overflow, extent), margin) in
+                overflow.iter_mut().zip(shadow.extents()).zip(margin)
+            {
+                *overflow = (*overflow).max((extent - margin as f64).ceil() as i32);
             }
+        }
 
-            let margin = style.margin;
-            let padding = style.padding;
-
-            let width = width.unwrap_or(5);
-            let height = height.unwrap_or(5);
-
-            let surface = ImageSurface::create(
-                cairo::Format::ARgb32,
-                width + margin[1] + margin[3] + padding[1] + padding[3],
-                height + margin[0] + margin[2] + padding[0] + padding[2],
-            )
-            .map_err(|_| CssError::ContentError("Failed to create cairo surface"))?;
-            let mut img =
-                Vec::with_capacity(surface.width() as usize * surface.height() as usize * 4);
-
-            let context = Context::new(&surface)
-                .map_err(|_| CssError::ContentError("Failed to create cairo context"))?;
-
-            context.set_source_rgba(
-                style.background_color[0],
-                style.background_color[1],
-                style.background_color[2],
-                style.background_color[3],
-            );
-            draw_rectangle(
-                &context,
-                margin[3] as f64,
-                margin[0] as f64,
-                width as f64 + padding[1] as f64 + padding[3] as f64,
-                height as f64 + padding[0] as f64 + padding[2] as f64,
-                style.border_radius,
-            );
-            context
-                .fill_preserve()
-                .map_err(|_| CssError::ContentError("Failed to paint the surface"))?;
-
-            if let Some(text) = &style.content {
-                let font = &style.font;
-                context.select_font_face(font.family.deref(), font.style, font.weight);
-                context.set_font_size(font.size);
-                context.set_source_rgba(font.color[0], font.color[1], font.color[2], 1.0);
-                match font.text_align.deref() {
-                    "center" => {
-                        context.move_to(
-                            (width / 2 - text_width / 2) as f64 + padding[3] as f64,
-                            position as f64 + padding[0] as f64,
-                        );
-                    }
-                    "right" => {
-                        context.move_to(width as f64 - text_width as f64, position as f64);
-                    }
-                    "left" => {
-                        context.move_to(
-                            0.0 + padding[3] as f64 + margin[3] as f64,
-                            position as f64 + padding[0] as f64 + margin[0] as f64,
-                        );
+        // Text shadows reach past the content box of auto sized images, which fits the text
+        if style.content.is_some() {
+            for shadow in &style.font.text_shadow {
+                for (side, extent) in shadow.extents().into_iter().enumerate() {
+                    let auto = match side % 2 {
+                        0 => style.height.is_none(),
+                        _ => style.width.is_none(),
+                    };
+                    if auto {
+                        let inset = margin[side] + border[side] + padding[side];
+                        overflow[side] = overflow[side].max((extent - inset as f64).ceil() as i32);
                     }
-                    _ => return Err(CssError::ContentError("Invalid text-align")),
                 }
-                _ = context.show_text(text.deref());
             }
+        }
+    }
 
-            surface
-                .write_to_png(&mut img)
-                .map_err(|_| CssError::ContentError("Failed to write cairo surface as PNG"))?;
+    let surface = ImageSurface::create(
+        cairo::Format::ARgb32,
+        border_box.2 as i32 + margin[1] + margin[3] + overflow[1] + overflow[3],
+        border_box.3 as i32 + margin[0] + margin[2] + overflow[0] + overflow[2],
+    )
+    .map_err(|source| CssError::SurfaceCreation {
+        selector: name.clone(),
+        source,
+    })?;
+    let mut img = Vec::with_capacity(surface.width() as usize * surface.height() as usize * 4);
 
-            Ok((name.clone(), img))
-        })
-        .collect::<Result<HashMap<_, _>, CssError>>()
-}
+    let context = Context::new(&surface).map_err(|source| CssError::ContextCreation {
+        selector: name.clone(),
+        source,
+    })?;
 
-fn draw_rectangle(context: &Context, x: f64, y: f64, width: f64, height: f64, border_radius: f64) {
-    let border_radius = match border_radius > 20. {
-        true => 20. / 3.33,
-        false => border_radius / 3.33,
-    };
-    let degrees = std::f64::consts::PI / 180.0;
-
-    context.new_sub_path();
-    context.arc(
-        x + width - border_radius,
-        y + border_radius,
-        border_radius,
-        -90.0 * degrees,
-        0.0 * degrees,
-    );
-    context.arc(
-        x + width - border_radius,
-        y + height - border_radius,
-        border_radius,
-        0.0 * degrees,
-        90.0 * degrees,
-    );
-    context.arc(
-        x + border_radius,
-        y + height - border_radius,
-        border_radius,
-        90.0 * degrees,
-        180.0 * degrees,
-    );
-    context.arc(
-        x + border_radius,
-        y + border_radius,
-        border_radius,
-        180.0 * degrees,
-        270.0 * degrees,
+    context.translate(overflow[3] as f64, overflow[0] as f64);
+    draw_shadows(&context, style, border_box, false).map_err(|source| CssError::Paint {
+        selector: name.clone(),
+        source,
+    })?;
+
+    // Everything else is clipped to the rounded border box
+    rounded_rectangle(
+        &context,
+        border_box.0,
+        border_box.1,
+        border_box.2,
+        border_box.3,
+        style.border.radii(border_box.2, border_box.3),
     );
-    context.close_path();
+    context.clip();
+
+    draw_background(&context, style, border_box, options, false).map_err(|source| {
+        CssError::Paint {
+            selector: name.clone(),
+            source,
+        }
+    })?;
+
+    draw_shadows(&context, style, border_box, true).map_err(|source| CssError::Paint {
+        selector: name.clone(),
+        source,
+    })?;
+
+    style
+        .border
+        .draw(
+            &context,
+            border_box.0,
+            border_box.1,
+            border_box.2,
+            border_box.3,
+        )
+        .map_err(|source| CssError::Paint {
+            selector: name.clone(),
+            source,
+        })?;
+
+    if style.content.is_some() {
+        let font = &style.font;
+
+        // Each line is aligned on its own, `start` and `end` following its direction
+        let origins = text
+            .lines
+            .iter()
+            .map(|line| {
+                let align = match (font.text_align.deref(), line.rtl) {
+                    ("start", false) | ("end", true) => "left",
+                    ("start", true) | ("end", false) => "right",
+                    (align, _) => align,
+                };
+                let x = match align {
+                    "center" => (f64::from(width) - line.width) / 2.,
+                    "right" => f64::from(width) - line.width,
+                    "left" => 0.,
+                    value => {
+                        return Err(CssError::InvalidTextAlign {
+                            selector: name.clone(),
+                            value: value.to_string(),
+                        })
+                    }
+                };
+                Ok((
+                    f64::from(content_x) + x.round() - line.left,
+                    f64::from(content_y) + line.baseline,
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Text shadows are drawn the last one first, and aren't clipped to the border box
+        let bounds = text_bounds(&text, &origins, style);
+        for shadow in font.text_shadow.iter().rev() {
+            context
+                .save()
+                .and_then(|_| {
+                    context.reset_clip();
+                    shadow.draw_text(&context, bounds, |context| {
+                        draw_text(context, style, &text, &origins)
+                    })?;
+                    context.restore()
+                })
+                .map_err(|source| CssError::Paint {
+                    selector: name.clone(),
+                    source,
+                })?;
+        }
+
+        // The background only shows through the glyphs, under the text's own color
+        if style
+            .background
+            .iter()
+            .any(|layer| layer.clip == BackgroundClip::Text)
+        {
+            context
+                .save()
+                .and_then(|_| {
+                    for (line, origin) in text.lines.iter().zip(&origins) {
+                        context.move_to(origin.0, origin.1);
+                        pangocairo::functions::layout_line_path(&context, &line.line);
+                    }
+                    context.clip();
+                    draw_background(&context, style, border_box, options, true)?;
+                    context.restore()
+                })
+                .map_err(|source| CssError::Paint {
+                    selector: name.clone(),
+                    source,
+                })?;
+        }
+
+        draw_text(&context, style, &text, &origins).map_err(|source| CssError::TextRendering {
+            selector: name.clone(),
+            source,
+        })?;
+    }
+
+    surface
+        .write_to_png(&mut img)
+        .map_err(|source| CssError::PngEncoding {
+            selector: name.clone(),
+            source,
+        })?;
+
+    Ok(Rendered {
+        png: img,
+        font_families: text.families,
+    })
 }
@@ -0,0 +1,57 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    // Custom properties, e.g. `--accent`, overriding the ones declared in the stylesheet
+    pub variables: HashMap<String, String>,
+    // Width and height in px that `vw` and `vh` resolve against
+    pub viewport: Option<(f64, f64)>,
+    // Width and height in px that percentages resolve against
+    pub containing_block: Option<(f64, f64)>,
+    // Clip box shadows to the margin box instead of growing the image to fit them
+    pub clip_shadows: bool,
+    // Directory that relative `url()`s are loaded from, the working directory when None. Urls
+    // can't point outside of it
+    pub base_dir: Option<PathBuf>,
+    // Loads the images `url()`s point to instead of reading them from `base_dir`. Fonts are
+    // always read from `base_dir`, as they have to be files for fontconfig
+    pub resolver: Option<Resolver>,
+}
+
+impl RenderOptions {
+    // The file a `url()` points to in `base_dir`, None when it doesn't exist or is outside of it,
+    // be it through an absolute path, `..` or a symlink
+    pub(crate) fn file(&self, url: &str) -> Option<PathBuf> {
+        let base_dir = self.base_dir.as_deref().unwrap_or(Path::new("."));
+        let base_dir = base_dir.canonicalize().ok()?;
+        let path = base_dir.join(url).canonicalize().ok()?;
+        path.starts_with(&base_dir).then_some(path)
+    }
+}
+
+type Resolve = dyn Fn(&str) -> Option<Vec<u8>> + Send + Sync;
+
+// Returns the contents of the resource at a url, or None when there is no such resource
+#[derive(Clone)]
+pub struct Resolver(Arc<Resolve>);
+
+impl Resolver {
+    pub fn new(resolve: impl Fn(&str) -> Option<Vec<u8>> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(resolve))
+    }
+
+    pub fn resolve(&self, url: &str) -> Option<Vec<u8>> {
+        (self.0)(url)
+    }
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Resolver")
+    }
+}
@@ -0,0 +1,692 @@
+pub(crate) mod tokenizer;
+
+use crate::{
+    diagnostics::{Diagnostic, LineIndex, Severity, Span},
+    selector::Selector,
+    style::{self, FontFace},
+    CssError,
+};
+use std::{collections::HashSet, ops::Range};
+use tokenizer::{Spanned, Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Declaration {
+    pub name: String,
+    pub value: String,
+    pub important: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Rule {
+    pub selectors: Vec<String>,
+    pub declarations: Vec<Declaration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stylesheet {
+    pub(crate) rules: Vec<Rule>,
+    // The span of each `@font-face` keyword and the descriptors in its block
+    pub(crate) font_faces: Vec<(Span, Vec<Declaration>)>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    // The selectors of each declaration that was dropped for a unit that doesn't exist, and
+    // the unit
+    pub(crate) unknown_units: Vec<(Vec<String>, String)>,
+}
+
+impl Stylesheet {
+    // Parses the stylesheet and drops the declarations `Style` can't make sense of
+    pub fn parse(css: &str) -> Self {
+        let mut stylesheet = parse_stylesheet(css);
+        let diagnostics = &mut stylesheet.diagnostics;
+        let unknown_units = &mut stylesheet.unknown_units;
+
+        stylesheet.rules.iter_mut().for_each(|rule| {
+            rule.declarations.retain(|declaration| {
+                match style::validate(&declaration.name, &declaration.value) {
+                    Ok(()) => true,
+                    Err((severity, message)) => {
+                        if let Some(unit) = style::unknown_unit(&declaration.value) {
+                            unknown_units.push((rule.selectors.clone(), unit));
+                        }
+                        diagnostics.push(Diagnostic {
+                            severity,
+                            span: declaration.span,
+                            selector: Some(rule.selectors.join(", ")),
+                            property: Some(declaration.name.clone()),
+                            value: Some(declaration.value.clone()),
+                            message: message.to_string(),
+                        });
+                        severity == Severity::Warning
+                    }
+                }
+            });
+        });
+
+        // Font faces that are missing a family or a usable source can never be matched
+        stylesheet.font_faces.retain_mut(|(span, declarations)| {
+            declarations.retain(|declaration| {
+                let (severity, message) =
+                    match style::validate_font_descriptor(&declaration.name, &declaration.value) {
+                        Some(Ok(())) => return true,
+                        Some(Err(message)) => (Severity::Error, message),
+                        None => (Severity::Warning, "Unknown descriptor"),
+                    };
+                diagnostics.push(Diagnostic {
+                    severity,
+                    span: declaration.span,
+                    selector: Some("@font-face".to_string()),
+                    property: Some(declaration.name.clone()),
+                    value: Some(declaration.value.clone()),
+                    message: message.to_string(),
+                });
+                false
+            });
+
+            let valid = FontFace::new(declarations).is_some();
+            if !valid {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    span: *span,
+                    selector: Some("@font-face".to_string()),
+                    property: None,
+                    value: None,
+                    message: "`@font-face` needs a font-family and a src".to_string(),
+                });
+            }
+            valid
+        });
+
+        stylesheet
+    }
+
+    // The faces of the `@font-face` rules, in order
+    pub(crate) fn font_faces(&self) -> Vec<FontFace> {
+        self.font_faces
+            .iter()
+            .filter_map(|(_, declarations)| FontFace::new(declarations))
+            .collect()
+    }
+
+    // Fails on the first declaration dropped for its unit, only looking at the rules that
+    // match `element` when there is one
+    pub(crate) fn check_units(&self, element: Option<&Selector>) -> Result<(), CssError> {
+        let unknown = self.unknown_units.iter().find(|(selectors, _)| {
+            element.is_none_or(|element| {
+                selectors
+                    .iter()
+                    .any(|selector| Selector::parse(selector).matches(element))
+            })
+        });
+
+        match unknown {
+            Some((selectors, unit)) => Err(CssError::UnknownUnit {
+                selector: selectors.join(", "),
+                unit: unit.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Every distinct selector in order of first appearance
+    pub(crate) fn selectors(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.rules
+            .iter()
+            .flat_map(|rule| &rule.selectors)
+            .filter(|selector| seen.insert(selector.as_str()))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+pub(crate) fn parse_stylesheet(css: &str) -> Stylesheet {
+    let source = tokenizer::preprocess(css);
+    let tokens = tokenizer::tokenize(&source);
+
+    let mut parser = Parser {
+        source: &source,
+        tokens: &tokens,
+        pos: 0,
+        lines: LineIndex::new(&source),
+        font_faces: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    let rules = parser.consume_rule_list();
+
+    Stylesheet {
+        rules,
+        font_faces: parser.font_faces,
+        diagnostics: parser.diagnostics,
+        unknown_units: Vec::new(),
+    }
+}
+
+// Concatenates the string tokens of a value, e.g. `"Hello, " "World!"`
+pub(crate) fn unquote(value: &str) -> Option<String> {
+    tokenizer::tokenize(value)
+        .into_iter()
+        .filter(|t| t.token != Token::Whitespace)
+        .map(|t| match t.token {
+            Token::String(s) => Some(s),
+            _ => None,
+        })
+        .collect()
+}
+
+// Splits a value on whitespace outside of functions and blocks, e.g. `calc(1px + 2px) 3px`
+pub(crate) fn split_values(value: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut depth = 0;
+    let mut start = None;
+
+    for t in tokenizer::tokenize(value) {
+        match t.token {
+            Token::Whitespace if depth == 0 => {
+                if let Some(start) = start.take() {
+                    values.push(&value[start..t.span.start]);
+                }
+                continue;
+            }
+            Token::Function(_) | Token::OpenParen | Token::OpenSquare | Token::OpenCurly => {
+                depth += 1
+            }
+            Token::CloseParen | Token::CloseSquare | Token::CloseCurly if depth > 0 => depth -= 1,
+            _ => {}
+        }
+        start.get_or_insert(t.span.start);
+    }
+
+    if let Some(start) = start {
+        values.push(&value[start..]);
+    }
+    values
+}
+
+// Splits a comma separated list outside of functions and blocks, e.g. `1px red, inset 0 0 rgb(0, 0, 0)`
+pub(crate) fn split_list(value: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for t in tokenizer::tokenize(value) {
+        match t.token {
+            Token::Comma if depth == 0 => {
+                values.push(value[start..t.span.start].trim());
+                start = t.span.end;
+            }
+            Token::Function(_) | Token::OpenParen | Token::OpenSquare | Token::OpenCurly => {
+                depth += 1
+            }
+            Token::CloseParen | Token::CloseSquare | Token::CloseCurly if depth > 0 => depth -= 1,
+            _ => {}
+        }
+    }
+
+    values.push(value[start..].trim());
+    values
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: &'a [Spanned],
+    pos: usize,
+    lines: LineIndex,
+    font_faces: Vec<(Span, Vec<Declaration>)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    fn offset(&self, index: usize) -> usize {
+        self.tokens
+            .get(index)
+            .map_or(self.source.len(), |t| t.span.start)
+    }
+
+    // Source span of a token range, leaving out surrounding whitespace
+    fn span(&self, range: Range<usize>) -> Span {
+        let tokens = &self.tokens[range.start.min(self.tokens.len())..range.end];
+        let significant = |t: &&Spanned| t.token != Token::Whitespace;
+        let start = tokens.iter().find(significant);
+        let end = tokens.iter().rev().find(significant);
+
+        match (start, end) {
+            (Some(start), Some(end)) => self.lines.span(start.span.start..end.span.end),
+            _ => self
+                .lines
+                .span(self.offset(range.start)..self.offset(range.end)),
+        }
+    }
+
+    fn report(
+        &mut self,
+        severity: Severity,
+        range: Range<usize>,
+        selector: Option<&str>,
+        message: &str,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            span: self.span(range),
+            selector: selector.map(str::to_string),
+            property: None,
+            value: None,
+            message: message.to_string(),
+        });
+    }
+
+    fn consume_rule_list(&mut self) -> Vec<Rule> {
+        let mut rules = Vec::new();
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Whitespace | Token::Cdo | Token::Cdc => self.pos += 1,
+                Token::AtKeyword(_) => self.consume_at_rule(),
+                _ => rules.extend(self.consume_qualified_rule()),
+            }
+        }
+        rules
+    }
+
+    // `@font-face` is the only at-rule supported, skip over the prelude and block of others
+    fn consume_at_rule(&mut self) {
+        let start = self.pos;
+        self.pos += 1;
+
+        let name = match self.tokens.get(start).map(|t| &t.token) {
+            Some(Token::AtKeyword(name)) => name.clone(),
+            _ => String::new(),
+        };
+        if name.eq_ignore_ascii_case("font-face") {
+            while let Some(Token::Whitespace) = self.peek() {
+                self.pos += 1;
+            }
+            if let Some(Token::OpenCurly) = self.peek() {
+                let block_start = self.pos;
+                let (block, closed) = self.consume_block();
+                if !closed {
+                    self.report(
+                        Severity::Error,
+                        block_start..block_start + 1,
+                        Some("@font-face"),
+                        "Unclosed block",
+                    );
+                }
+                let declarations = self.consume_declaration_list(block, "@font-face");
+                self.font_faces
+                    .push((self.span(start..start + 1), declarations));
+                return;
+            }
+        }
+
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Semicolon => {
+                    self.pos += 1;
+                    break;
+                }
+                Token::OpenCurly => {
+                    self.consume_component_value();
+                    break;
+                }
+                _ => {
+                    self.consume_component_value();
+                }
+            }
+        }
+
+        let message = format!("Unsupported at-rule `@{name}` was ignored");
+        self.report(Severity::Warning, start..start + 1, None, &message);
+    }
+
+    fn consume_qualified_rule(&mut self) -> Option<Rule> {
+        let start = self.pos;
+        let mut commas = Vec::new();
+        while let Some(token) = self.peek() {
+            match token {
+                Token::OpenCurly => break,
+                Token::Comma => commas.push(self.pos),
+                _ => {}
+            }
+            self.consume_component_value();
+        }
+
+        if self.peek().is_none() {
+            self.report(
+                Severity::Error,
+                start..self.pos,
+                None,
+                "Expected `{` after selector",
+            );
+            return None;
+        }
+
+        let prelude = self.serialize(start..self.pos);
+        let selectors = std::iter::once(start)
+            .chain(commas.iter().map(|comma| comma + 1))
+            .zip(commas.iter().copied().chain(std::iter::once(self.pos)))
+            .map(|(start, end)| self.serialize(start..end))
+            .collect::<Vec<_>>();
+        let valid = selectors.iter().all(|selector| !selector.is_empty());
+        if !valid {
+            self.report(
+                Severity::Error,
+                start..self.pos + 1,
+                None,
+                "Expected a selector",
+            );
+        }
+
+        let block_start = self.pos;
+        let (block, closed) = self.consume_block();
+        if !closed {
+            self.report(
+                Severity::Error,
+                block_start..block_start + 1,
+                Some(&prelude),
+                "Unclosed block",
+            );
+        }
+        let declarations = self.consume_declaration_list(block, &prelude);
+
+        match valid {
+            true => Some(Rule {
+                selectors,
+                declarations,
+            }),
+            false => None,
+        }
+    }
+
+    // Returns the token range inside of the block and whether it was closed
+    fn consume_block(&mut self) -> (Range<usize>, bool) {
+        let start = self.pos + 1;
+        match self.consume_component_value() {
+            true => (start..self.pos - 1, true),
+            false => (start..self.pos, false),
+        }
+    }
+
+    // Returns false if a block or function was left unclosed
+    fn consume_component_value(&mut self) -> bool {
+        let closing = match self.peek() {
+            Some(Token::OpenCurly) => Token::CloseCurly,
+            Some(Token::OpenSquare) => Token::CloseSquare,
+            Some(Token::OpenParen | Token::Function(_)) => Token::CloseParen,
+            Some(_) => {
+                self.pos += 1;
+                return true;
+            }
+            None => return true,
+        };
+
+        self.pos += 1;
+        while let Some(token) = self.peek() {
+            if *token == closing {
+                self.pos += 1;
+                return true;
+            }
+            self.consume_component_value();
+        }
+        false
+    }
+
+    fn consume_declaration_list(
+        &mut self,
+        block: Range<usize>,
+        selector: &str,
+    ) -> Vec<Declaration> {
+        let mut declarations = Vec::new();
+        let (after, end) = (self.pos, block.end);
+        self.pos = block.start;
+
+        while self.pos < end {
+            match self.peek() {
+                Some(Token::Whitespace | Token::Semicolon) => self.pos += 1,
+                Some(token) => {
+                    let is_ident = matches!(token, Token::Ident(_));
+                    let start = self.pos;
+                    while self.pos < end && self.peek() != Some(&Token::Semicolon) {
+                        self.consume_component_value();
+                    }
+                    let range = start..self.pos.min(end);
+                    match is_ident {
+                        true => declarations.extend(self.consume_declaration(range, selector)),
+                        false => self.report(
+                            Severity::Error,
+                            range,
+                            Some(selector),
+                            "Expected a property name",
+                        ),
+                    }
+                }
+                None => break,
+            }
+        }
+
+        self.pos = after;
+        declarations
+    }
+
+    fn consume_declaration(&mut self, range: Range<usize>, selector: &str) -> Option<Declaration> {
+        let tokens: &'a [Spanned] = &self.tokens[range.clone()];
+        let name = match &tokens.first()?.token {
+            Token::Ident(name) if name.starts_with("--") => name.clone(),
+            Token::Ident(name) => name.to_ascii_lowercase(),
+            _ => return None,
+        };
+
+        let invalid = |parser: &mut Self, message: &str, value: Option<String>| {
+            parser.report(Severity::Error, range.clone(), Some(selector), message);
+            if let Some(diagnostic) = parser.diagnostics.last_mut() {
+                diagnostic.property = Some(name.clone());
+                diagnostic.value = value;
+            }
+            None
+        };
+
+        let colon = tokens
+            .iter()
+            .skip(1)
+            .position(|t| t.token != Token::Whitespace)
+            .map(|i| i + 1);
+        let colon = match colon {
+            Some(i) if tokens[i].token == Token::Colon => i,
+            _ => return invalid(self, "Expected `:` after property name", None),
+        };
+
+        let mut value = range.start + colon + 1..range.end;
+        let mut important = false;
+        let mut significant = tokens
+            .iter()
+            .enumerate()
+            .skip(colon + 1)
+            .rev()
+            .filter(|(_, t)| t.token != Token::Whitespace);
+        if let (Some((_, last)), Some((i, bang))) = (significant.next(), significant.next()) {
+            if matches!(&last.token, Token::Ident(s) if s.eq_ignore_ascii_case("important"))
+                && bang.token == Token::Delim('!')
+            {
+                important = true;
+                value.end = range.start + i;
+            }
+        }
+
+        let serialized = self.serialize(value.clone());
+        if serialized.is_empty() {
+            return invalid(self, "Expected a value", None);
+        }
+        let malformed = self.tokens[value]
+            .iter()
+            .any(|t| matches!(t.token, Token::BadString | Token::BadUrl));
+        if malformed {
+            return invalid(self, "Unterminated string or url", Some(serialized));
+        }
+
+        Some(Declaration {
+            name,
+            value: serialized,
+            important,
+            span: self.span(range),
+        })
+    }
+
+    // Joins the source of the tokens, collapsing whitespace and dropping comments
+    fn serialize(&self, range: Range<usize>) -> String {
+        let mut value = String::new();
+        for t in &self.tokens[range] {
+            match t.token {
+                Token::Whitespace => value.push(' '),
+                _ => value.push_str(&self.source[t.span.clone()]),
+            }
+        }
+        value.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stylesheet() {
+        let css = r#"
+        /* comment } */
+        my widget {
+            content: "}";
+            width: 100px !important;
+            COLOR: red
+        }
+        @media print { a { b: c } }
+        two{height:5px;;}
+        "#;
+
+        let stylesheet = parse_stylesheet(css);
+        assert_eq!(stylesheet.rules.len(), 2);
+
+        let rule = &stylesheet.rules[0];
+        assert_eq!(rule.selectors, vec!["my widget"]);
+        assert_eq!(rule.declarations.len(), 3);
+        assert_eq!(rule.declarations[0].name, "content");
+        assert_eq!(rule.declarations[0].value, "\"}\"");
+        assert_eq!(rule.declarations[1].value, "100px");
+        assert!(rule.declarations[1].important);
+        assert_eq!(rule.declarations[2].name, "color");
+        assert_eq!(rule.declarations[2].value, "red");
+
+        let rule = &stylesheet.rules[1];
+        assert_eq!(rule.selectors, vec!["two"]);
+        assert_eq!(rule.declarations[0].value, "5px");
+    }
+
+    #[test]
+    fn test_invalid_declarations() {
+        let css = "a { width 10px; : 5px; height: rgb(1; 2); content: \"a\nb; color: red }";
+        let stylesheet = parse_stylesheet(css);
+        let declarations = &stylesheet.rules[0].declarations;
+        assert_eq!(declarations.len(), 2);
+        assert_eq!(declarations[0].value, "rgb(1; 2)");
+        assert_eq!(declarations[1].value, "red");
+
+        let messages = stylesheet
+            .diagnostics
+            .iter()
+            .map(|d| (d.span.start.column, d.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                (5, "Expected `:` after property name"),
+                (17, "Expected a property name"),
+                (43, "Unterminated string or url"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let stylesheet = parse_stylesheet("@media print {}\n{ a: b }\nb { c: d");
+        let diagnostics = stylesheet
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.span.start.line, d.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    Severity::Warning,
+                    1,
+                    "Unsupported at-rule `@media` was ignored"
+                ),
+                (Severity::Error, 2, "Expected a selector"),
+                (Severity::Error, 3, "Unclosed block"),
+            ]
+        );
+        assert_eq!(stylesheet.rules.len(), 1);
+        assert_eq!(stylesheet.rules[0].declarations[0].value, "d");
+    }
+
+    #[test]
+    fn test_font_face() {
+        let stylesheet = parse_stylesheet(
+            "@font-face { font-family: A; src: url(a.ttf) }\n@FONT-FACE{src:local(B)} a { b: c }",
+        );
+        assert!(stylesheet.diagnostics.is_empty());
+        assert_eq!(stylesheet.rules.len(), 1);
+
+        let faces = stylesheet
+            .font_faces
+            .iter()
+            .map(|(span, declarations)| (span.start.line, declarations.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(faces, [(1, 2), (2, 1)]);
+        assert_eq!(stylesheet.font_faces[1].1[0].value, "local(B)");
+    }
+
+    #[test]
+    fn test_selector_lists() {
+        let stylesheet = parse_stylesheet("one , two,three:is(a, b) { a: b } a, { b: c }");
+        assert_eq!(stylesheet.rules.len(), 1);
+        assert_eq!(
+            stylesheet.rules[0].selectors,
+            vec!["one", "two", "three:is(a, b)"]
+        );
+        assert_eq!(stylesheet.diagnostics[0].message, "Expected a selector");
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(
+            unquote(r#""Hello, " "World!""#).as_deref(),
+            Some("Hello, World!")
+        );
+        assert_eq!(unquote(r#"'\'quoted\''"#).as_deref(), Some("'quoted'"));
+        assert_eq!(unquote("unquoted"), None);
+    }
+
+    #[test]
+    fn test_split_values() {
+        assert_eq!(
+            split_values(" 1px calc(2px + 3px)  min(1em, 4px) "),
+            ["1px", "calc(2px + 3px)", "min(1em, 4px)"]
+        );
+        assert!(split_values("").is_empty());
+    }
+
+    #[test]
+    fn test_split_list() {
+        assert_eq!(
+            split_list("1px red, inset 0 0 rgb(0, 0, 0) ,2px"),
+            ["1px red", "inset 0 0 rgb(0, 0, 0)", "2px"]
+        );
+        assert_eq!(split_list("1px,"), ["1px", ""]);
+    }
+}
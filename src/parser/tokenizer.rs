@@ -0,0 +1,453 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Ident(String),
+    Function(String),
+    AtKeyword(String),
+    Hash(String, bool),
+    String(String),
+    BadString,
+    Url(String),
+    BadUrl,
+    Delim(char),
+    Number(f64),
+    Percentage(f64),
+    Dimension(f64, String),
+    Whitespace,
+    Cdo,
+    Cdc,
+    Colon,
+    Semicolon,
+    Comma,
+    OpenSquare,
+    CloseSquare,
+    OpenParen,
+    CloseParen,
+    OpenCurly,
+    CloseCurly,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Spanned {
+    pub token: Token,
+    pub span: Range<usize>,
+}
+
+// https://www.w3.org/TR/css-syntax-3/#input-preprocessing
+pub(crate) fn preprocess(css: &str) -> String {
+    css.replace("\r\n", "\n")
+        .replace(['\r', '\x0C'], "\n")
+        .replace('\0', "\u{FFFD}")
+}
+
+pub(crate) fn tokenize(css: &str) -> Vec<Spanned> {
+    let mut tokenizer = Tokenizer { input: css, pos: 0 };
+    let mut tokens = Vec::new();
+
+    loop {
+        tokenizer.consume_comments();
+        let start = tokenizer.pos;
+        match tokenizer.consume_token() {
+            Some(token) => tokens.push(Spanned {
+                token,
+                span: start..tokenizer.pos,
+            }),
+            None => return tokens,
+        }
+    }
+}
+
+struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn is_name(c: char) -> bool {
+    is_name_start(c) || c.is_ascii_digit() || c == '-'
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n')
+}
+
+fn is_non_printable(c: char) -> bool {
+    matches!(c, '\0'..='\x08' | '\x0B' | '\x0E'..='\x1F' | '\x7F')
+}
+
+impl Tokenizer<'_> {
+    fn peek(&self, n: usize) -> Option<char> {
+        self.input[self.pos..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn consume_comments(&mut self) {
+        while self.input[self.pos..].starts_with("/*") {
+            match self.input[self.pos + 2..].find("*/") {
+                Some(end) => self.pos += end + 4,
+                None => self.pos = self.input.len(),
+            }
+        }
+    }
+
+    fn consume_token(&mut self) -> Option<Token> {
+        let c = self.bump()?;
+        let token = match c {
+            c if is_whitespace(c) => {
+                while self.peek(0).is_some_and(is_whitespace) {
+                    self.bump();
+                }
+                Token::Whitespace
+            }
+            '"' | '\'' => self.consume_string(c),
+            '#' => {
+                if self.peek(0).is_some_and(is_name) || self.is_valid_escape(0) {
+                    let id = self.starts_ident(0);
+                    Token::Hash(self.consume_name(), id)
+                } else {
+                    Token::Delim('#')
+                }
+            }
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '[' => Token::OpenSquare,
+            ']' => Token::CloseSquare,
+            '{' => Token::OpenCurly,
+            '}' => Token::CloseCurly,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            ';' => Token::Semicolon,
+            '+' | '.' if self.starts_number(-1) => self.consume_numeric(c),
+            '-' if self.starts_number(-1) => self.consume_numeric(c),
+            '-' if self.input[self.pos..].starts_with("->") => {
+                self.pos += 2;
+                Token::Cdc
+            }
+            '-' if self.starts_ident(-1) => self.consume_ident_like(c),
+            '<' if self.input[self.pos..].starts_with("!--") => {
+                self.pos += 3;
+                Token::Cdo
+            }
+            '@' if self.starts_ident(0) => Token::AtKeyword(self.consume_name()),
+            '\\' if self.is_valid_escape(-1) => self.consume_ident_like(c),
+            c if c.is_ascii_digit() => self.consume_numeric(c),
+            c if is_name_start(c) => self.consume_ident_like(c),
+            c => Token::Delim(c),
+        };
+
+        Some(token)
+    }
+
+    // Offsets are relative to the next code point, -1 being the one just consumed
+    fn peek_from(&self, offset: isize, n: usize) -> Option<char> {
+        match offset {
+            -1 => self.input[..self.pos]
+                .chars()
+                .next_back()
+                .and_then(|c| match n {
+                    0 => Some(c),
+                    n => self.peek(n - 1),
+                }),
+            offset => self.peek(offset as usize + n),
+        }
+    }
+
+    fn is_valid_escape(&self, offset: isize) -> bool {
+        self.peek_from(offset, 0) == Some('\\')
+            && self.peek_from(offset, 1) != Some('\n')
+            && self.peek_from(offset, 1).is_some()
+    }
+
+    fn starts_ident(&self, offset: isize) -> bool {
+        match self.peek_from(offset, 0) {
+            Some('-') => {
+                self.peek_from(offset, 1)
+                    .is_some_and(|c| is_name_start(c) || c == '-')
+                    || (self.peek_from(offset, 1) == Some('\\')
+                        && self.peek_from(offset, 2).is_some_and(|c| c != '\n'))
+            }
+            Some('\\') => self.is_valid_escape(offset),
+            Some(c) => is_name_start(c),
+            None => false,
+        }
+    }
+
+    fn starts_number(&self, offset: isize) -> bool {
+        let digit = |n| {
+            self.peek_from(offset, n)
+                .is_some_and(|c: char| c.is_ascii_digit())
+        };
+        match self.peek_from(offset, 0) {
+            Some('+' | '-') => digit(1) || (self.peek_from(offset, 1) == Some('.') && digit(2)),
+            Some('.') => digit(1),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    fn consume_escape(&mut self) -> char {
+        match self.bump() {
+            Some(c) if c.is_ascii_hexdigit() => {
+                let mut hex = String::from(c);
+                while hex.len() < 6 && self.peek(0).is_some_and(|c| c.is_ascii_hexdigit()) {
+                    hex.extend(self.bump());
+                }
+                if self.peek(0).is_some_and(is_whitespace) {
+                    self.bump();
+                }
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|&code| code != 0)
+                    .and_then(char::from_u32)
+                    .unwrap_or('\u{FFFD}')
+            }
+            Some(c) => c,
+            None => '\u{FFFD}',
+        }
+    }
+
+    fn consume_name(&mut self) -> String {
+        let mut name = String::new();
+        loop {
+            match self.peek(0) {
+                Some(c) if is_name(c) => {
+                    name.push(c);
+                    self.bump();
+                }
+                Some('\\') if self.is_valid_escape(0) => {
+                    self.bump();
+                    name.push(self.consume_escape());
+                }
+                _ => return name,
+            }
+        }
+    }
+
+    fn consume_number(&mut self, first: char) -> f64 {
+        let start = self.pos - first.len_utf8();
+        let digits = |tokenizer: &mut Self| {
+            while tokenizer.peek(0).is_some_and(|c| c.is_ascii_digit()) {
+                tokenizer.bump();
+            }
+        };
+
+        digits(self);
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+            digits(self);
+        }
+        if matches!(self.peek(0), Some('e' | 'E')) {
+            let exponent = match self.peek(1) {
+                Some('+' | '-') => self.peek(2).is_some_and(|c| c.is_ascii_digit()),
+                Some(c) => c.is_ascii_digit(),
+                None => false,
+            };
+            if exponent {
+                self.bump();
+                if matches!(self.peek(0), Some('+' | '-')) {
+                    self.bump();
+                }
+                digits(self);
+            }
+        }
+
+        let repr = &self.input[start..self.pos];
+        repr.strip_prefix('+').unwrap_or(repr).parse().unwrap_or(0.)
+    }
+
+    fn consume_numeric(&mut self, first: char) -> Token {
+        let value = self.consume_number(first);
+        if self.starts_ident(0) {
+            Token::Dimension(value, self.consume_name())
+        } else if self.peek(0) == Some('%') {
+            self.bump();
+            Token::Percentage(value)
+        } else {
+            Token::Number(value)
+        }
+    }
+
+    fn consume_ident_like(&mut self, first: char) -> Token {
+        self.pos -= first.len_utf8();
+        let name = self.consume_name();
+
+        if self.peek(0) != Some('(') {
+            return Token::Ident(name);
+        }
+        self.bump();
+
+        if name.eq_ignore_ascii_case("url") {
+            while self.peek(0).is_some_and(is_whitespace) && self.peek(1).is_some_and(is_whitespace)
+            {
+                self.bump();
+            }
+            let quoted = |c: Option<char>| matches!(c, Some('"' | '\''));
+            if quoted(self.peek(0))
+                || (self.peek(0).is_some_and(is_whitespace) && quoted(self.peek(1)))
+            {
+                return Token::Function(name);
+            }
+            return self.consume_url();
+        }
+
+        Token::Function(name)
+    }
+
+    fn consume_string(&mut self, quote: char) -> Token {
+        let mut value = String::new();
+        loop {
+            match self.peek(0) {
+                None => return Token::String(value),
+                Some(c) if c == quote => {
+                    self.bump();
+                    return Token::String(value);
+                }
+                Some('\n') => return Token::BadString,
+                Some('\\') => {
+                    self.bump();
+                    match self.peek(0) {
+                        None => {}
+                        Some('\n') => {
+                            self.bump();
+                        }
+                        Some(_) => value.push(self.consume_escape()),
+                    }
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    fn consume_url(&mut self) -> Token {
+        let mut value = String::new();
+        while self.peek(0).is_some_and(is_whitespace) {
+            self.bump();
+        }
+
+        loop {
+            match self.bump() {
+                None | Some(')') => return Token::Url(value),
+                Some(c) if is_whitespace(c) => {
+                    while self.peek(0).is_some_and(is_whitespace) {
+                        self.bump();
+                    }
+                    return match self.peek(0) {
+                        None => Token::Url(value),
+                        Some(')') => {
+                            self.bump();
+                            Token::Url(value)
+                        }
+                        Some(_) => self.consume_bad_url(),
+                    };
+                }
+                Some('"' | '\'' | '(') => return self.consume_bad_url(),
+                Some(c) if is_non_printable(c) => return self.consume_bad_url(),
+                Some('\\') if self.is_valid_escape(-1) => value.push(self.consume_escape()),
+                Some('\\') => return self.consume_bad_url(),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn consume_bad_url(&mut self) -> Token {
+        loop {
+            match self.bump() {
+                None | Some(')') => return Token::BadUrl,
+                Some('\\') if self.is_valid_escape(-1) => {
+                    self.consume_escape();
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(css: &str) -> Vec<Token> {
+        tokenize(css).into_iter().map(|t| t.token).collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokens("body{width:100px}"),
+            vec![
+                Token::Ident("body".into()),
+                Token::OpenCurly,
+                Token::Ident("width".into()),
+                Token::Colon,
+                Token::Dimension(100., "px".into()),
+                Token::CloseCurly,
+            ]
+        );
+
+        assert_eq!(
+            tokens("/* a } */ -1.5e2% +.5 #fff #1a rgb("),
+            vec![
+                Token::Whitespace,
+                Token::Percentage(-150.),
+                Token::Whitespace,
+                Token::Number(0.5),
+                Token::Whitespace,
+                Token::Hash("fff".into(), true),
+                Token::Whitespace,
+                Token::Hash("1a".into(), false),
+                Token::Whitespace,
+                Token::Function("rgb".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_and_escapes() {
+        assert_eq!(tokens(r#""}\"""#), vec![Token::String("}\"".into())]);
+        assert_eq!(tokens(r"'\41 b'"), vec![Token::String("Ab".into())]);
+        assert_eq!(tokens("\"a\nb"), {
+            vec![
+                Token::BadString,
+                Token::Whitespace,
+                Token::Ident("b".into()),
+            ]
+        });
+        assert_eq!(tokens(r"\.icon"), vec![Token::Ident(".icon".into())]);
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(tokens("url( a.png )"), vec![Token::Url("a.png".into())]);
+        assert_eq!(
+            tokens("url(\"a.png\")"),
+            vec![
+                Token::Function("url".into()),
+                Token::String("a.png".into()),
+                Token::CloseParen,
+            ]
+        );
+        assert_eq!(tokens("url(a b)"), vec![Token::BadUrl]);
+    }
+
+    #[test]
+    fn test_spans() {
+        let css = "a /* b */ { }";
+        let spans = tokenize(css)
+            .into_iter()
+            .map(|t| &css[t.span])
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec!["a", " ", " ", "{", " ", "}"]);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::parser::tokenizer::{self, Token};
+use std::fmt;
+
+// (ids, classes, types)
+pub(crate) type Specificity = (u32, u32, u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Selector {
+    Compound {
+        tag: Option<String>,
+        id: Option<String>,
+        classes: Vec<String>,
+        pseudo_classes: Vec<String>,
+    },
+    // Selectors using syntax we can't match on, e.g. combinators, only match themselves
+    Literal(String),
+}
+
+// An element to render, matched against compound selectors, e.g. `battery#main.charging`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Element {
+    pub tag: Option<String>,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+}
+
+impl Element {
+    // Returns None if the selector isn't a single compound selector
+    pub fn parse(selector: &str) -> Option<Self> {
+        match Selector::parse(selector) {
+            Selector::Compound {
+                tag,
+                id,
+                classes,
+                pseudo_classes,
+            } if pseudo_classes.is_empty() => Some(Self { tag, id, classes }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.tag, &self.id, self.classes.is_empty()) {
+            (None, None, true) => write!(f, "*")?,
+            (tag, id, _) => {
+                tag.iter().try_for_each(|tag| write!(f, "{tag}"))?;
+                id.iter().try_for_each(|id| write!(f, "#{id}"))?;
+            }
+        }
+        self.classes
+            .iter()
+            .try_for_each(|class| write!(f, ".{class}"))
+    }
+}
+
+impl From<&Element> for Selector {
+    fn from(element: &Element) -> Self {
+        Selector::Compound {
+            tag: element.tag.clone(),
+            id: element.id.clone(),
+            classes: element.classes.clone(),
+            pseudo_classes: Vec::new(),
+        }
+    }
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Self {
+        let literal = || Selector::Literal(selector.to_string());
+
+        let mut tag = None;
+        let mut id = None;
+        let mut classes = Vec::new();
+        let mut pseudo_classes = Vec::new();
+
+        let tokens = tokenizer::tokenize(selector);
+        let mut tokens = tokens.iter().map(|t| &t.token);
+        let mut first = true;
+        while let Some(token) = tokens.next() {
+            match token {
+                Token::Ident(name) if first => tag = Some(name.clone()),
+                Token::Delim('*') if first => {}
+                Token::Hash(name, true) if id.is_none() => id = Some(name.clone()),
+                Token::Delim('.') => match tokens.next() {
+                    Some(Token::Ident(class)) => classes.push(class.clone()),
+                    _ => return literal(),
+                },
+                Token::Colon => match tokens.next() {
+                    Some(Token::Ident(class)) => pseudo_classes.push(class.to_ascii_lowercase()),
+                    _ => return literal(),
+                },
+                _ => return literal(),
+            }
+            first = false;
+        }
+
+        match first {
+            true => literal(),
+            false => Selector::Compound {
+                tag,
+                id,
+                classes,
+                pseudo_classes,
+            },
+        }
+    }
+
+    pub fn specificity(&self) -> Specificity {
+        match self {
+            Selector::Compound {
+                tag,
+                id,
+                classes,
+                pseudo_classes,
+            } => (
+                id.is_some() as u32,
+                (classes.len() + pseudo_classes.len()) as u32,
+                tag.is_some() as u32,
+            ),
+            Selector::Literal(_) => (0, 0, 1),
+        }
+    }
+
+    // Every rendered element is the root of its own document, so `:root` always matches
+    pub fn matches(&self, element: &Selector) -> bool {
+        let root = |pseudo_classes: &Vec<String>| pseudo_classes.iter().all(|p| p == "root");
+
+        match (self, element) {
+            (
+                Selector::Compound {
+                    tag,
+                    id,
+                    classes,
+                    pseudo_classes,
+                },
+                Selector::Compound {
+                    tag: element_tag,
+                    id: element_id,
+                    classes: element_classes,
+                    ..
+                },
+            ) => {
+                (tag.is_none() || tag == element_tag)
+                    && (id.is_none() || id == element_id)
+                    && classes.iter().all(|class| element_classes.contains(class))
+                    && root(pseudo_classes)
+            }
+            (
+                Selector::Compound {
+                    tag: None,
+                    id: None,
+                    classes,
+                    pseudo_classes,
+                },
+                Selector::Literal(_),
+            ) => classes.is_empty() && root(pseudo_classes),
+            (Selector::Literal(selector), Selector::Literal(element)) => selector == element,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Selector::parse("button#ok.icon.large"),
+            Selector::Compound {
+                tag: Some("button".into()),
+                id: Some("ok".into()),
+                classes: vec!["icon".into(), "large".into()],
+                pseudo_classes: vec![],
+            }
+        );
+        assert_eq!(
+            Selector::parse("*"),
+            Selector::Compound {
+                tag: None,
+                id: None,
+                classes: vec![],
+                pseudo_classes: vec![],
+            }
+        );
+        assert_eq!(
+            Selector::parse("status bar"),
+            Selector::Literal("status bar".into())
+        );
+        assert_eq!(Selector::parse("a#b#c"), Selector::Literal("a#b#c".into()));
+    }
+
+    #[test]
+    fn test_element() {
+        let element = Element::parse("battery#main.charging.low").unwrap();
+        assert_eq!(element.tag.as_deref(), Some("battery"));
+        assert_eq!(element.id.as_deref(), Some("main"));
+        assert_eq!(element.classes, vec!["charging", "low"]);
+        assert_eq!(element.to_string(), "battery#main.charging.low");
+
+        assert_eq!(Element::default().to_string(), "*");
+        assert!(Element::parse("status bar").is_none());
+    }
+
+    #[test]
+    fn test_specificity() {
+        assert_eq!(Selector::parse("*").specificity(), (0, 0, 0));
+        assert_eq!(Selector::parse("a").specificity(), (0, 0, 1));
+        assert_eq!(Selector::parse(".a.b").specificity(), (0, 2, 0));
+        assert_eq!(Selector::parse("a#b.c").specificity(), (1, 1, 1));
+        assert_eq!(Selector::parse(":root").specificity(), (0, 1, 0));
+    }
+
+    #[test]
+    fn test_matches() {
+        let element = Selector::parse("#battery.icon.charging");
+        assert!(Selector::parse("*").matches(&element));
+        assert!(Selector::parse(".icon").matches(&element));
+        assert!(Selector::parse("#battery.charging").matches(&element));
+        assert!(!Selector::parse("#clock").matches(&element));
+        assert!(!Selector::parse("span.icon").matches(&element));
+        assert!(Selector::parse(":root").matches(&element));
+        assert!(!Selector::parse(".icon:hover").matches(&element));
+
+        let element = Selector::parse("status bar");
+        assert!(Selector::parse("*").matches(&element));
+        assert!(Selector::parse(":root").matches(&element));
+        assert!(Selector::parse("status bar").matches(&element));
+        assert!(!Selector::parse("status").matches(&element));
+    }
+}
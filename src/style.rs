@@ -1,30 +1,85 @@
+mod background;
+mod border;
+mod calc;
+mod color;
+mod decoration;
 mod font;
-
+mod font_face;
+mod gradient;
+mod length;
+mod shadow;
+mod text;
+
+use crate::cascade::SIDES;
+use crate::diagnostics::Severity;
 use crate::error::CssError;
-use crate::parse;
+use crate::{compute_styles, parse, parser, RenderOptions, Stylesheet};
+pub(crate) use background::{
+    split_shorthand as split_background, LONGHANDS as BACKGROUND_LONGHANDS,
+};
+pub use background::{
+    BackgroundClip, BackgroundLayer, BackgroundOrigin, BackgroundSize, Image, Position, Repeat,
+};
+pub(crate) use border::{
+    inset_rectangle, rounded_rectangle, split_radius, split_shorthand as split_border, CORNERS,
+};
+pub use border::{Border, BorderStyle, Radius};
+use color::{get_color, INITIAL_COLOR};
+pub(crate) use decoration::{
+    split_shorthand as split_text_decoration, split_stroke as split_text_stroke,
+    LONGHANDS as TEXT_DECORATION_LONGHANDS,
+};
+pub use decoration::{DecorationStyle, TextDecoration, TextStroke};
 use font::Font;
+pub use font::{FontStretch, FontWeight};
+pub(crate) use font_face::validate_descriptor as validate_font_descriptor;
+pub use font_face::{FontFace, FontSource};
+pub use gradient::{ColorStop, Direction, Gradient, GradientKind, RadialSize};
+pub(crate) use length::unknown_unit;
+pub use length::LengthPercentage;
+use length::{Length, LengthContext};
+pub use shadow::Shadow;
 use std::collections::HashMap;
+pub(crate) use text::layout as layout_text;
+pub use text::{
+    DecorationMetrics, Line, LineHeight, TextDirection, TextLayout, UnicodeBidi, WhiteSpace,
+};
 
 pub trait Parseable {
-    fn parse(self) -> Result<Vec<Style>, CssError<'static>>;
+    fn parse(self) -> Result<Vec<Style>, CssError>;
+
+    // Already computed styles ignore the options
+    fn parse_with_options(self, _options: &RenderOptions) -> Result<Vec<Style>, CssError>
+    where
+        Self: Sized,
+    {
+        self.parse()
+    }
 }
 
 impl Parseable for Vec<Style> {
-    fn parse(self) -> Result<Vec<Style>, CssError<'static>> {
+    fn parse(self) -> Result<Vec<Style>, CssError> {
         Ok(self)
     }
 }
 
 impl Parseable for Style {
-    fn parse(self) -> Result<Vec<Style>, CssError<'static>> {
+    fn parse(self) -> Result<Vec<Style>, CssError> {
         Ok(vec![self])
     }
 }
 
 impl Parseable for &str {
-    fn parse(self) -> Result<Vec<Style>, CssError<'static>> {
+    fn parse(self) -> Result<Vec<Style>, CssError> {
         parse(self)
     }
+
+    fn parse_with_options(self, options: &RenderOptions) -> Result<Vec<Style>, CssError> {
+        let stylesheet = Stylesheet::parse(self);
+        stylesheet.check_units(None)?;
+
+        Ok(compute_styles(&stylesheet, options))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -32,51 +87,75 @@ pub struct Style {
     pub selector: String,
     pub width: Option<i32>,
     pub height: Option<i32>,
-    pub background_color: [f64; 4],
+    // From the top, ending with the background color
+    pub background: Vec<BackgroundLayer>,
     pub font: Font,
+    pub border: Border,
+    // Drawn in order from the top
+    pub box_shadow: Vec<Shadow>,
     pub content: Option<Box<str>>,
-    pub border_radius: f64,
     pub margin: [i32; 4],
     pub padding: [i32; 4],
 }
 
-pub(super) fn get_color(color: &str) -> [f64; 4] {
-    if color.starts_with('#') {
-        let r = u8::from_str_radix(color.get(1..3).unwrap_or("00"), 16).unwrap_or(0) as f64 / 255.;
-        let g = u8::from_str_radix(color.get(3..5).unwrap_or("00"), 16).unwrap_or(0) as f64 / 255.;
-        let b = u8::from_str_radix(color.get(5..7).unwrap_or("00"), 16).unwrap_or(0) as f64 / 255.;
-        let a =
-            u8::from_str_radix(color.get(7..9).unwrap_or("ff"), 16).unwrap_or(255) as f64 / 255.;
-        [r, g, b, a]
-    } else if color.starts_with("rgba") {
-        let rgba = color[4..color.len() - 1]
-            .replace(['(', ')'], "")
-            .split(',')
-            .map(|s| s.trim().parse().unwrap_or(0) as f64 / 255.)
-            .collect::<Vec<_>>();
-        if rgba.len() == 4 {
-            [rgba[0], rgba[1], rgba[2], rgba[3]]
-        } else {
-            [0., 0., 0., 1.]
+pub(super) fn validate_length(
+    value: &str,
+    negative: bool,
+    percent: bool,
+) -> Result<(), &'static str> {
+    match Length::parse(value) {
+        Some(length) if length.is_percent() && !percent => Err("Expected a length"),
+        Some(length) if length.is_negative() && !negative => Err("Expected a non-negative length"),
+        Some(_) => Ok(()),
+        None if percent => Err("Expected a length or percentage"),
+        None => Err("Expected a length"),
+    }
+}
+
+// Checks a declaration against what `Style::new` is able to parse
+pub(crate) fn validate(property: &str, value: &str) -> Result<(), (Severity, &'static str)> {
+    let invalid = |message| Err((Severity::Error, message));
+
+    // Values depending on variables can only be checked once they are substituted
+    if property.starts_with("--") || value.contains("var(") {
+        return Ok(());
+    }
+
+    match property {
+        "width" | "height" | "padding-top" | "padding-right" | "padding-bottom"
+        | "padding-left" => validate_length(value, false, true).or_else(invalid),
+        "margin-top" | "margin-right" | "margin-bottom" | "margin-left" => {
+            validate_length(value, true, true).or_else(invalid)
         }
-    } else if color.starts_with("rgb") {
-        let rgb: Vec<f64> = color[4..color.len() - 1]
-            .replace(['(', ')'], "")
-            .split(',')
-            .map(|s| s.trim().parse().unwrap_or(0) as f64 / 255.)
-            .collect();
-        if rgb.len() == 3 {
-            [rgb[0], rgb[1], rgb[2], 1.]
-        } else {
-            [0., 0., 0., 1.]
+        "margin" | "padding" => {
+            let values = parser::split_values(value);
+            if values.is_empty() || values.len() > 4 {
+                return invalid("Expected one to four lengths");
+            }
+            values
+                .iter()
+                .try_for_each(|v| validate_length(v, property == "margin", true))
+                .or_else(invalid)
         }
-    } else {
-        match color.replace('"', "").as_str() {
-            "red" => [1., 0., 0., 1.],
-            "green" => [0., 1., 0., 1.],
-            "blue" => [0., 0., 1., 1.],
-            "white" => [1., 1., 1., 1.],
-            _ => [0., 0., 0., 1.],
+        "background-color" => match get_color(value, INITIAL_COLOR) {
+            Some(_) => Ok(()),
+            None => invalid("Invalid color"),
+        },
+        "content" => match parser::unquote(value) {
+            Some(_) => Ok(()),
+            None => invalid("Expected a string"),
+        },
+        property => {
+            match font::validate(property, value)
+                .or_else(|| text::validate(property, value))
+                .or_else(|| decoration::validate(property, value))
+                .or_else(|| border::validate(property, value))
+                .or_else(|| shadow::validate(property, value))
+                .or_else(|| background::validate(property, value))
+            {
+                Some(result) => result.map_err(|message| (Severity::Error, message)),
+                None => Err((Severity::Warning, "Unknown property")),
+            }
         }
     }
 }
@@ -85,66 +164,41 @@ impl Style {
     pub(crate) fn new(
         selector: String,
         css: &HashMap<Box<str>, String>,
-        all_selector: Option<&HashMap<Box<str>, String>>,
+        options: &RenderOptions,
+        faces: &[FontFace],
     ) -> Self {
-        let get_property = |property: &str| {
+        let font = Font::new(css, options, faces);
+        let context = LengthContext {
+            font_size: font.size,
+            root_font_size: font.size,
+            viewport: options.viewport,
+        };
+        let block = options.containing_block;
+
+        // Percentages of width, margin and padding are relative to the containing block's
+        // width, only those of height are relative to its height. Math functions can end up
+        // negative, which only margins allow
+        let get_length = |property: &str, percent_basis: Option<f64>| {
             css.get(property)
-                .or_else(|| all_selector.as_ref()?.get(property))
-                .and_then(|s| s.strip_suffix("px"))
-                .and_then(|s| s.parse::<i32>().ok())
+                .and_then(|s| Length::parse(s))
+                .and_then(|length| length.resolve(&context, percent_basis))
+                .map(|px| match property.starts_with("margin") {
+                    true => px,
+                    false => px.max(0.),
+                })
+        };
+        let get_property = |property: &str| {
+            get_length(property, block.map(|(width, _)| width)).map(|px| px.round() as i32)
         };
 
         let width = get_property("width");
-        let height = get_property("height");
-        let border_radius = get_property("border-radius").unwrap_or(0) as f64;
+        let height =
+            get_length("height", block.map(|(_, height)| height)).map(|px| px.round() as i32);
 
-        let background_color = css
-            .get("background-color")
-            .or_else(|| all_selector.as_ref()?.get("background-color"))
-            .map(|color| get_color(color))
-            .unwrap_or([0., 0., 0., 1.]);
+        let background = background::layers(css, &context, font.color);
 
         let get_padding_or_margin = |property: &str| {
-            let directions = [
-                format!("{}-top", property),
-                format!("{}-right", property),
-                format!("{}-bottom", property),
-                format!("{}-left", property),
-            ];
-
-            let mut values = css
-                .get(property)
-                .or_else(|| all_selector.as_ref()?.get(property))
-                .map_or([0; 4], |value| {
-                    let values: Vec<i32> = value
-                        .split_whitespace()
-                        .filter_map(|s| match s.ends_with("px") {
-                            true => s.replace("px", "").parse().ok(),
-                            false => None,
-                        })
-                        .collect();
-                    match values.len() {
-                        1 => [values[0]; 4],
-                        2 => [values[0], values[1], values[0], values[1]],
-                        3 => [values[0], values[1], values[2], values[1]],
-                        4 => [values[0], values[1], values[2], values[3]],
-                        _ => [0; 4],
-                    }
-                });
-
-            directions.iter().for_each(|direction| {
-                if let Some(value) = get_property(direction) {
-                    match direction.as_str().split('-').last().unwrap() {
-                        "top" => values[0] = value,
-                        "right" => values[1] = value,
-                        "bottom" => values[2] = value,
-                        "left" => values[3] = value,
-                        _ => {}
-                    }
-                }
-            });
-
-            values
+            SIDES.map(|side| get_property(&format!("{property}-{side}")).unwrap_or(0))
         };
 
         let padding = get_padding_or_margin("padding");
@@ -152,21 +206,37 @@ impl Style {
 
         let content = css
             .get("content")
-            .or_else(|| all_selector.as_ref()?.get("content"))
-            .map(|s| s.trim().replace('"', "").into());
+            .and_then(|s| parser::unquote(s))
+            .map(|s| s.into());
 
-        let font = Font::new(css, all_selector);
+        let border = Border::new(css, &context, font.color);
+        let box_shadow = css
+            .get("box-shadow")
+            .and_then(|value| Shadow::parse_list(value, &context, font.color))
+            .unwrap_or_default();
 
         Self {
             selector,
             padding,
             margin,
-            border_radius,
             content,
             font,
+            border,
+            box_shadow,
             width,
             height,
-            background_color,
+            background,
+        }
+    }
+
+    // The color of the last background layer, under all of the images
+    pub fn background_color(&self) -> [f64; 4] {
+        match self.background.last() {
+            Some(BackgroundLayer {
+                image: Image::Color(color),
+                ..
+            }) => *color,
+            _ => [0.; 4],
         }
     }
 }
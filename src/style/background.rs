@@ -0,0 +1,872 @@
+use super::calc::Calc;
+use super::color::INITIAL_COLOR;
+use super::get_color;
+use super::gradient::Gradient;
+use super::length::{Length, LengthContext, LengthPercentage};
+use crate::parser::{
+    self,
+    tokenizer::{self, Token},
+};
+use crate::RenderOptions;
+use cairo::{Context, ImageSurface};
+use std::collections::HashMap;
+
+type Rect = (f64, f64, f64, f64);
+
+// The longhands the `background` shorthand expands into, along with their initial values
+pub(crate) const LONGHANDS: [(&str, &str); 7] = [
+    ("background-color", "transparent"),
+    ("background-image", "none"),
+    ("background-position", "0% 0%"),
+    ("background-size", "auto"),
+    ("background-repeat", "repeat"),
+    ("background-origin", "padding-box"),
+    ("background-clip", "border-box"),
+];
+
+// https://www.w3.org/TR/css-backgrounds-3/#layering
+// Each image is a layer along with its own size, position, repeat, origin and clip
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundLayer {
+    pub image: Image,
+    pub size: BackgroundSize,
+    pub position: Position,
+    // Horizontal and vertical
+    pub repeat: [Repeat; 2],
+    pub origin: BackgroundOrigin,
+    pub clip: BackgroundClip,
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#background-image
+#[derive(Debug, Clone, PartialEq)]
+pub enum Image {
+    // The `background-color` under every other layer, filling its whole clip
+    Color([f64; 4]),
+    // Loaded once rendering, through `RenderOptions::resolver` or from `RenderOptions::base_dir`
+    Url(String),
+    Gradient(Box<Gradient>),
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#background-size
+// Sizes left as None are `auto`
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackgroundSize {
+    Cover,
+    Contain,
+    Explicit(Option<LengthPercentage>, Option<LengthPercentage>),
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#background-repeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Repeat {
+    #[default]
+    Repeat,
+    Space,
+    Round,
+    NoRepeat,
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#background-origin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackgroundOrigin {
+    BorderBox,
+    #[default]
+    PaddingBox,
+    ContentBox,
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#background-clip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackgroundClip {
+    #[default]
+    BorderBox,
+    PaddingBox,
+    ContentBox,
+    // Only painted inside of the glyphs of the content
+    Text,
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#typedef-bg-position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub x: LengthPercentage,
+    pub y: LengthPercentage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl BackgroundClip {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let clip = match value.trim().to_ascii_lowercase().as_str() {
+            "border-box" => BackgroundClip::BorderBox,
+            "padding-box" => BackgroundClip::PaddingBox,
+            "content-box" => BackgroundClip::ContentBox,
+            "text" => BackgroundClip::Text,
+            _ => return None,
+        };
+
+        Some(clip)
+    }
+}
+
+impl Default for BackgroundSize {
+    fn default() -> Self {
+        BackgroundSize::Explicit(None, None)
+    }
+}
+
+impl BackgroundOrigin {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let origin = match value.trim().to_ascii_lowercase().as_str() {
+            "border-box" => BackgroundOrigin::BorderBox,
+            "padding-box" => BackgroundOrigin::PaddingBox,
+            "content-box" => BackgroundOrigin::ContentBox,
+            _ => return None,
+        };
+
+        Some(origin)
+    }
+}
+
+impl Repeat {
+    // The horizontal and vertical repeat, e.g. `repeat-x` or `space no-repeat`
+    pub(crate) fn parse(value: &str) -> Option<[Self; 2]> {
+        let repeat = |value: &str| match value.to_ascii_lowercase().as_str() {
+            "repeat" => Some(Repeat::Repeat),
+            "space" => Some(Repeat::Space),
+            "round" => Some(Repeat::Round),
+            "no-repeat" => Some(Repeat::NoRepeat),
+            _ => None,
+        };
+
+        match *parser::split_values(value) {
+            [value] if value.eq_ignore_ascii_case("repeat-x") => {
+                Some([Repeat::Repeat, Repeat::NoRepeat])
+            }
+            [value] if value.eq_ignore_ascii_case("repeat-y") => {
+                Some([Repeat::NoRepeat, Repeat::Repeat])
+            }
+            [value] => repeat(value).map(|repeat| [repeat; 2]),
+            [x, y] => Some([repeat(x)?, repeat(y)?]),
+            _ => None,
+        }
+    }
+}
+
+impl BackgroundSize {
+    pub(crate) fn parse(value: &str, context: &LengthContext) -> Option<Self> {
+        let size = |value: &str| match value.eq_ignore_ascii_case("auto") {
+            true => Some(None),
+            false => Length::parse(value)
+                .filter(|length| !length.is_negative())
+                .map(|length| Some(LengthPercentage::new(length, context))),
+        };
+
+        match *parser::split_values(value) {
+            [value] if value.eq_ignore_ascii_case("cover") => Some(BackgroundSize::Cover),
+            [value] if value.eq_ignore_ascii_case("contain") => Some(BackgroundSize::Contain),
+            [value] => Some(BackgroundSize::Explicit(size(value)?, None)),
+            [x, y] => Some(BackgroundSize::Explicit(size(x)?, size(y)?)),
+            _ => None,
+        }
+    }
+
+    // The size of a tile inside of the positioning area, gradients have no size of their own
+    // and fill it where `auto`
+    fn resolve(&self, intrinsic: Option<(f64, f64)>, (width, height): (f64, f64)) -> (f64, f64) {
+        let intrinsic = intrinsic.filter(|&(x, y)| x > 0. && y > 0.);
+        match (self, intrinsic) {
+            (BackgroundSize::Cover | BackgroundSize::Contain, Some((x, y))) => {
+                let scale = match self {
+                    BackgroundSize::Cover => (width / x).max(height / y),
+                    _ => (width / x).min(height / y),
+                };
+                (x * scale, y * scale)
+            }
+            (BackgroundSize::Cover | BackgroundSize::Contain, None) => (width, height),
+            (BackgroundSize::Explicit(x, y), intrinsic) => {
+                let x = x.as_ref().map(|x| x.resolve(width).max(0.));
+                let y = y.as_ref().map(|y| y.resolve(height).max(0.));
+                match (x, y, intrinsic) {
+                    (Some(x), Some(y), _) => (x, y),
+                    (Some(x), None, Some((width, height))) => (x, x * height / width),
+                    (None, Some(y), Some((width, height))) => (y * width / height, y),
+                    (None, None, Some(intrinsic)) => intrinsic,
+                    (x, y, None) => (x.unwrap_or(width), y.unwrap_or(height)),
+                }
+            }
+        }
+    }
+}
+
+impl Image {
+    pub(crate) fn parse(
+        value: &str,
+        context: &LengthContext,
+        current_color: [f64; 4],
+    ) -> Option<Self> {
+        let tokens = tokenizer::tokenize(value.trim());
+        let tokens = tokens
+            .iter()
+            .map(|t| &t.token)
+            .filter(|t| **t != Token::Whitespace)
+            .collect::<Vec<_>>();
+
+        match tokens.as_slice() {
+            [Token::Url(url)] => Some(Image::Url(url.clone())),
+            [Token::Function(function), Token::String(url), Token::CloseParen]
+                if function.eq_ignore_ascii_case("url") =>
+            {
+                Some(Image::Url(url.clone()))
+            }
+            _ => Gradient::parse(value, context, current_color)
+                .map(|gradient| Image::Gradient(Box::new(gradient))),
+        }
+    }
+}
+
+// Images that can't be loaded or decoded aren't drawn, like in browsers. Only PNGs are supported
+fn load(url: &str, options: &RenderOptions) -> Option<ImageSurface> {
+    let data = match &options.resolver {
+        Some(resolver) => resolver.resolve(url)?,
+        None => std::fs::read(options.file(url)?).ok()?,
+    };
+
+    ImageSurface::create_from_png(&mut data.as_slice()).ok()
+}
+
+// Where the tiles along one axis start, so that they cover the painting area. Spaced out tiles
+// fill the positioning area exactly, unless only one of them fits
+fn tiles(
+    repeat: Repeat,
+    position: f64,
+    size: f64,
+    area: (f64, f64),
+    painting: (f64, f64),
+) -> Vec<f64> {
+    let (start, step) = match repeat {
+        Repeat::NoRepeat => return vec![area.0 + position],
+        Repeat::Space => {
+            let count = (area.1 / size).floor();
+            if count < 2. {
+                return vec![area.0 + position];
+            }
+            (area.0, size + (area.1 - count * size) / (count - 1.))
+        }
+        Repeat::Repeat | Repeat::Round => (area.0 + position, size),
+    };
+
+    let first = start - ((start - painting.0) / step).ceil() * step;
+    std::iter::successors(Some(first), |x| Some(x + step))
+        .take_while(|x| *x < painting.0 + painting.1)
+        .collect()
+}
+
+// The axis a keyword is on, and the percentage it stands for
+fn keyword(value: &str) -> Option<(Axis, f64)> {
+    let keyword = match value.to_ascii_lowercase().as_str() {
+        "left" => (Axis::Horizontal, 0.),
+        "right" => (Axis::Horizontal, 100.),
+        "top" => (Axis::Vertical, 0.),
+        "bottom" => (Axis::Vertical, 100.),
+        "center" => (Axis::Both, 50.),
+        _ => return None,
+    };
+
+    Some(keyword)
+}
+
+// The initial `background-position` is the top left corner
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            x: LengthPercentage::percent(0.),
+            y: LengthPercentage::percent(0.),
+        }
+    }
+}
+
+impl Position {
+    pub(crate) fn center() -> Self {
+        Self {
+            x: LengthPercentage::percent(50.),
+            y: LengthPercentage::percent(50.),
+        }
+    }
+
+    // One or two keywords or lengths, or two keywords each followed by an offset from that edge,
+    // e.g. `right 10px bottom 5px`
+    pub(crate) fn parse(values: &[&str], context: &LengthContext) -> Option<Self> {
+        let length = |value: &str| Length::parse(value).map(|l| LengthPercentage::new(l, context));
+        let component = |value: &str, axis: Axis| match keyword(value) {
+            Some((keyword, percent)) if keyword == axis || keyword == Axis::Both => {
+                Some(LengthPercentage::percent(percent))
+            }
+            Some(_) => None,
+            None => length(value),
+        };
+
+        let (x, y) = match *values {
+            [value] => match keyword(value) {
+                Some((Axis::Vertical, _)) => ("center", value),
+                _ => (value, "center"),
+            },
+            [first, second] => match (keyword(first), keyword(second)) {
+                (Some((Axis::Vertical, _)), Some(_)) | (Some(_), Some((Axis::Horizontal, _))) => {
+                    (second, first)
+                }
+                _ => (first, second),
+            },
+            [first, first_offset, second, second_offset] => {
+                let (first_axis, first_percent) = keyword(first)?;
+                let (second_axis, second_percent) = keyword(second)?;
+                let edge = |percent: f64, offset: &str| {
+                    let offset = Length::parse(offset)?;
+                    let length = match percent == 0. {
+                        true => offset,
+                        false => Length::Calc(Box::new(Calc::Difference(
+                            Box::new(Calc::Length(Length::Percent(percent))),
+                            Box::new(Calc::Length(offset)),
+                        ))),
+                    };
+                    Some(LengthPercentage::new(length, context))
+                };
+                let first = edge(first_percent, first_offset)?;
+                let second = edge(second_percent, second_offset)?;
+                return match (first_axis, second_axis) {
+                    (Axis::Horizontal, Axis::Vertical) => Some(Self {
+                        x: first,
+                        y: second,
+                    }),
+                    (Axis::Vertical, Axis::Horizontal) => Some(Self {
+                        x: second,
+                        y: first,
+                    }),
+                    _ => None,
+                };
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            x: component(x, Axis::Horizontal)?,
+            y: component(y, Axis::Vertical)?,
+        })
+    }
+}
+
+impl BackgroundLayer {
+    // Draws the image sized and positioned inside of the positioning area, and tiled across
+    // the painting area, which the caller has clipped to
+    pub(crate) fn draw(
+        &self,
+        context: &Context,
+        options: &RenderOptions,
+        positioning_area: Rect,
+        painting_area: Rect,
+    ) -> Result<(), cairo::Error> {
+        let image = &self.image;
+        let surface = match image {
+            Image::Color(color) => {
+                context.set_source_rgba(color[0], color[1], color[2], color[3]);
+                return context.paint();
+            }
+            Image::Url(url) => match load(url, options) {
+                Some(surface) => Some(surface),
+                None => return Ok(()),
+            },
+            Image::Gradient(_) => None,
+        };
+        let intrinsic = surface
+            .as_ref()
+            .map(|surface| (f64::from(surface.width()), f64::from(surface.height())));
+
+        let (x, y, width, height) = positioning_area;
+        let size = &self.size;
+        let (tile_width, tile_height) = size.resolve(intrinsic, (width, height));
+
+        // Rounded tiles are scaled to fit a whole number of times, and keep their aspect ratio when
+        // the other side is `auto`
+        let [repeat_x, repeat_y] = self.repeat;
+        let round = |tile: f64, area: f64| area / (area / tile).round().max(1.);
+        let (mut rounded_width, mut rounded_height) = (tile_width, tile_height);
+        if repeat_x == Repeat::Round {
+            rounded_width = round(tile_width, width);
+        }
+        if repeat_y == Repeat::Round {
+            rounded_height = round(tile_height, height);
+        }
+        match (repeat_x == Repeat::Round, repeat_y == Repeat::Round, size) {
+            (true, false, BackgroundSize::Explicit(_, None)) => {
+                rounded_height = tile_height * rounded_width / tile_width
+            }
+            (false, true, BackgroundSize::Explicit(None, _)) => {
+                rounded_width = tile_width * rounded_height / tile_height
+            }
+            _ => {}
+        }
+        let (tile_width, tile_height) = (rounded_width, rounded_height);
+        if !(tile_width > 0. && tile_height > 0.) {
+            return Ok(());
+        }
+
+        // Percentages line up the same point of the tile and of the positioning area
+        let position = &self.position;
+        let columns = tiles(
+            repeat_x,
+            position.x.resolve(width - tile_width),
+            tile_width,
+            (x, width),
+            (painting_area.0, painting_area.2),
+        );
+        let rows = tiles(
+            repeat_y,
+            position.y.resolve(height - tile_height),
+            tile_height,
+            (y, height),
+            (painting_area.1, painting_area.3),
+        );
+
+        for column in &columns {
+            for row in &rows {
+                context.save()?;
+                context.rectangle(*column, *row, tile_width, tile_height);
+                context.clip();
+                match (&surface, image) {
+                    (Some(surface), _) => {
+                        context.translate(*column, *row);
+                        context.scale(
+                            tile_width / f64::from(surface.width()),
+                            tile_height / f64::from(surface.height()),
+                        );
+                        context.set_source_surface(surface, 0., 0.)?;
+                        // Scaled up images would otherwise fade out towards their edges
+                        context.source().set_extend(cairo::Extend::Pad);
+                        context.paint()?;
+                    }
+                    (None, Image::Gradient(gradient)) => {
+                        gradient.draw(context, *column, *row, tile_width, tile_height)?
+                    }
+                    (None, Image::Color(_) | Image::Url(_)) => {}
+                }
+                context.restore()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The layers of `background-image` from the top, above a last one for `background-color` with
+// the clip of the bottom image. The lists of the other properties repeat to match the images
+pub(crate) fn layers<'a>(
+    css: &'a HashMap<Box<str>, String>,
+    context: &LengthContext,
+    current_color: [f64; 4],
+) -> Vec<BackgroundLayer> {
+    let list = |property: &str| {
+        css.get(property)
+            .map(|value| parser::split_list(value))
+            .unwrap_or_default()
+    };
+    let images = list("background-image");
+    let [sizes, positions, repeats, origins, clips] = [
+        "background-size",
+        "background-position",
+        "background-repeat",
+        "background-origin",
+        "background-clip",
+    ]
+    .map(list);
+    let get = |list: &Vec<&'a str>, i: usize| list.get(i % list.len().max(1)).copied();
+
+    let layer = |i: usize, image: Image| BackgroundLayer {
+        image,
+        size: get(&sizes, i)
+            .and_then(|size| BackgroundSize::parse(size, context))
+            .unwrap_or_default(),
+        position: get(&positions, i)
+            .and_then(|position| Position::parse(&parser::split_values(position), context))
+            .unwrap_or_default(),
+        repeat: get(&repeats, i).and_then(Repeat::parse).unwrap_or_default(),
+        origin: get(&origins, i)
+            .and_then(BackgroundOrigin::parse)
+            .unwrap_or_default(),
+        clip: get(&clips, i)
+            .and_then(BackgroundClip::parse)
+            .unwrap_or_default(),
+    };
+
+    let color = css
+        .get("background-color")
+        .and_then(|color| get_color(color, current_color))
+        .unwrap_or([0., 0., 0., 1.]);
+    let mut layers = images
+        .iter()
+        .enumerate()
+        .filter_map(|(i, image)| Some(layer(i, Image::parse(image, context, current_color)?)))
+        .collect::<Vec<_>>();
+    layers.push(layer(images.len().max(1) - 1, Image::Color(color)));
+    layers
+}
+
+// Splits a layer of the shorthand into its values, with `/` between the position and the size
+// being one of them
+fn words(value: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for value in parser::split_values(value) {
+        let mut start = 0;
+        let mut depth = 0;
+        for t in tokenizer::tokenize(value) {
+            match t.token {
+                Token::Function(_) | Token::OpenParen => depth += 1,
+                Token::CloseParen => depth -= 1,
+                Token::Delim('/') if depth == 0 => {
+                    words.extend([&value[start..t.span.start], "/"]);
+                    start = t.span.end;
+                }
+                _ => {}
+            }
+        }
+        words.push(&value[start..]);
+    }
+
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#background
+// Splits a layer of the shorthand into the values of the longhands in LONGHANDS, in any order.
+// Only the last layer can have a color
+fn split_layer(value: &str, last: bool) -> Option<[Option<String>; 7]> {
+    let context = LengthContext::default();
+    let words = words(value);
+    if words.is_empty() {
+        return None;
+    }
+
+    let mut split: [Option<String>; 7] = Default::default();
+    let mut boxes = Vec::new();
+    let mut attachment = false;
+    let mut i = 0;
+    while let Some(&word) = words.get(i) {
+        i += 1;
+        let keyword = word.to_ascii_lowercase();
+        let position = |length: usize| {
+            let words = words.get(i - 1..i - 1 + length)?;
+            Position::parse(words, &context).map(|_| words.join(" "))
+        };
+
+        if matches!(
+            keyword.as_str(),
+            "border-box" | "padding-box" | "content-box" | "text"
+        ) {
+            boxes.push(keyword);
+        } else if matches!(keyword.as_str(), "scroll" | "fixed" | "local") && !attachment {
+            // There is nothing to scroll, so the attachment makes no difference
+            attachment = true;
+        } else if split[4].is_none() && Repeat::parse(word).is_some() {
+            let pair = words.get(i).map(|next| format!("{word} {next}"));
+            split[4] = match pair.filter(|pair| Repeat::parse(pair).is_some()) {
+                Some(pair) => {
+                    i += 1;
+                    Some(pair)
+                }
+                None => Some(word.to_string()),
+            };
+        } else if split[1].is_none()
+            && (keyword == "none" || Image::parse(word, &context, INITIAL_COLOR).is_some())
+        {
+            split[1] = Some(word.to_string());
+        } else if split[2].is_none() && position(1).is_some() {
+            // As many values as make up a position, followed by the size after a `/`
+            let (length, position) = [4, 2, 1]
+                .into_iter()
+                .find_map(|length| Some((length, position(length)?)))?;
+            split[2] = Some(position);
+            i += length - 1;
+            if words.get(i) == Some(&"/") {
+                let (length, size) = [2, 1].into_iter().find_map(|length| {
+                    let size = words.get(i + 1..i + 1 + length)?.join(" ");
+                    BackgroundSize::parse(&size, &context).map(|_| (length, size))
+                })?;
+                split[3] = Some(size);
+                i += 1 + length;
+            }
+        } else if last && split[0].is_none() && get_color(word, INITIAL_COLOR).is_some() {
+            split[0] = Some(word.to_string());
+        } else {
+            return None;
+        }
+    }
+
+    // One box is both the origin and the clip, two are the origin and then the clip
+    match boxes.as_slice() {
+        [] => {}
+        [clip] if clip == "text" => split[6] = Some(clip.clone()),
+        [both] => [split[5], split[6]] = [Some(both.clone()), Some(both.clone())],
+        [origin, clip] if origin != "text" => {
+            [split[5], split[6]] = [Some(origin.clone()), Some(clip.clone())]
+        }
+        _ => return None,
+    }
+
+    Some(split)
+}
+
+// Splits the `background` shorthand into a comma separated list for each of the longhands in
+// LONGHANDS, filling in the initial values of the ones a layer leaves out
+pub(crate) fn split_shorthand(value: &str) -> Option<[String; 7]> {
+    let layers = parser::split_list(value);
+    let mut lists: [Vec<String>; 7] = Default::default();
+
+    for (i, layer) in layers.iter().enumerate() {
+        let last = i == layers.len() - 1;
+        let split = split_layer(layer, last)?;
+        for (j, value) in split.into_iter().enumerate() {
+            // There is a single color, from the last layer
+            if j == 0 && !last {
+                continue;
+            }
+            lists[j].push(value.unwrap_or_else(|| LONGHANDS[j].1.to_string()));
+        }
+    }
+
+    Some(lists.map(|list| list.join(", ")))
+}
+
+fn valid_image(value: &str) -> bool {
+    value.trim().eq_ignore_ascii_case("none")
+        || Image::parse(value, &LengthContext::default(), INITIAL_COLOR).is_some()
+}
+
+// Checks each value in a comma separated list of them, one for each layer
+fn list(
+    value: &str,
+    valid: impl Fn(&str) -> bool,
+    message: &'static str,
+) -> Result<(), &'static str> {
+    match parser::split_list(value).into_iter().all(valid) {
+        true => Ok(()),
+        false => Err(message),
+    }
+}
+
+// Returns None for properties that are not background related
+pub(super) fn validate(property: &str, value: &str) -> Option<Result<(), &'static str>> {
+    let result = match property {
+        "background" => split_shorthand(value)
+            .map(|_| ())
+            .ok_or("Expected a list of background layers"),
+        "background-image" => list(value, valid_image, "Expected `none`, a url or a gradient"),
+        "background-size" => list(
+            value,
+            |size| BackgroundSize::parse(size, &LengthContext::default()).is_some(),
+            "Expected `cover`, `contain` or one or two sizes",
+        ),
+        "background-position" => list(
+            value,
+            |position| {
+                Position::parse(&parser::split_values(position), &LengthContext::default())
+                    .is_some()
+            },
+            "Expected a position",
+        ),
+        "background-repeat" => list(
+            value,
+            |repeat| Repeat::parse(repeat).is_some(),
+            "Expected one or two repeat keywords",
+        ),
+        "background-origin" => list(
+            value,
+            |origin| BackgroundOrigin::parse(origin).is_some(),
+            "Expected `border-box`, `padding-box` or `content-box`",
+        ),
+        "background-clip" => list(
+            value,
+            |clip| BackgroundClip::parse(clip).is_some(),
+            "Expected `border-box`, `padding-box`, `content-box` or `text`",
+        ),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position() {
+        let context = LengthContext::default();
+        let position = |value| {
+            Position::parse(&parser::split_values(value), &context)
+                .map(|p| (p.x.resolve(200.), p.y.resolve(100.)))
+        };
+
+        assert_eq!(position("center"), Some((100., 50.)));
+        assert_eq!(position("top"), Some((100., 0.)));
+        assert_eq!(position("10px"), Some((10., 50.)));
+        assert_eq!(position("top right"), Some((200., 0.)));
+        assert_eq!(position("left 25%"), Some((0., 25.)));
+        assert_eq!(position("bottom 10px right 20px"), Some((180., 90.)));
+        assert_eq!(position("left 10px top 5px"), Some((10., 5.)));
+
+        assert_eq!(position("10px left"), None);
+        assert_eq!(position("left right"), None);
+        assert_eq!(position("left 10px right 5px"), None);
+        assert_eq!(position("left top center"), None);
+    }
+
+    #[test]
+    fn test_image() {
+        let context = LengthContext::default();
+        let parse = |value| Image::parse(value, &context, INITIAL_COLOR);
+
+        assert_eq!(parse("url(a.png)"), Some(Image::Url("a.png".into())));
+        assert_eq!(
+            parse("url( 'icons/b.png' )"),
+            Some(Image::Url("icons/b.png".into()))
+        );
+        assert!(matches!(
+            parse("linear-gradient(red, blue)"),
+            Some(Image::Gradient(_))
+        ));
+        assert_eq!(parse("url(a b)"), None);
+        assert_eq!(parse("url('a.png' 'b.png')"), None);
+    }
+
+    #[test]
+    fn test_size_and_repeat() {
+        let context = LengthContext::default();
+        let size = |value, intrinsic| {
+            BackgroundSize::parse(value, &context).map(|size| size.resolve(intrinsic, (100., 50.)))
+        };
+
+        assert_eq!(size("auto", Some((10., 20.))), Some((10., 20.)));
+        assert_eq!(size("auto", None), Some((100., 50.)));
+        assert_eq!(size("cover", Some((10., 20.))), Some((100., 200.)));
+        assert_eq!(size("contain", Some((10., 20.))), Some((25., 50.)));
+        assert_eq!(size("50%", Some((10., 20.))), Some((50., 100.)));
+        assert_eq!(size("auto 10px", None), Some((100., 10.)));
+        assert_eq!(size("-1px", None), None);
+        assert_eq!(size("cover contain", None), None);
+
+        assert_eq!(
+            Repeat::parse("repeat-y"),
+            Some([Repeat::NoRepeat, Repeat::Repeat])
+        );
+        assert_eq!(
+            Repeat::parse("space round"),
+            Some([Repeat::Space, Repeat::Round])
+        );
+        assert_eq!(Repeat::parse("repeat-x repeat"), None);
+    }
+
+    #[test]
+    fn test_tiles() {
+        let painting = (-5., 30.);
+        assert_eq!(tiles(Repeat::NoRepeat, 3., 10., (0., 20.), painting), [3.]);
+        assert_eq!(
+            tiles(Repeat::Repeat, 3., 10., (0., 20.), painting),
+            [-7., 3., 13., 23.]
+        );
+        assert_eq!(
+            tiles(Repeat::Space, 3., 6., (0., 20.), painting),
+            [-7., 0., 7., 14., 21.]
+        );
+        assert_eq!(tiles(Repeat::Space, 3., 11., (0., 20.), painting), [3.]);
+    }
+
+    #[test]
+    fn test_split_shorthand() {
+        let split = |value| split_shorthand(value).map(|values| values.map(String::from));
+
+        assert_eq!(
+            split("red"),
+            Some(
+                [
+                    "red",
+                    "none",
+                    "0% 0%",
+                    "auto",
+                    "repeat",
+                    "padding-box",
+                    "border-box"
+                ]
+                .map(String::from)
+            )
+        );
+        assert_eq!(
+            split("url(a.png) center / 50% auto no-repeat content-box, linear-gradient(red, blue) #fff text"),
+            Some(
+                [
+                    "#fff",
+                    "url(a.png), linear-gradient(red, blue)",
+                    "center, 0% 0%",
+                    "50% auto, auto",
+                    "no-repeat, repeat",
+                    "content-box, padding-box",
+                    "content-box, text",
+                ]
+                .map(String::from)
+            )
+        );
+        assert_eq!(
+            split("right 2px bottom 0/cover repeat-x fixed border-box padding-box #abc").map(
+                |values| [
+                    values[0].clone(),
+                    values[2].clone(),
+                    values[3].clone(),
+                    values[6].clone()
+                ]
+            ),
+            Some(["#abc", "right 2px bottom 0", "cover", "padding-box"].map(String::from))
+        );
+
+        assert_eq!(split("red blue"), None);
+        assert_eq!(split("red, url(a.png)"), None);
+        assert_eq!(split("url(a.png),"), None);
+        assert_eq!(split("center / foo"), None);
+        assert_eq!(split("text border-box"), None);
+        assert_eq!(split("foo"), None);
+    }
+
+    #[test]
+    fn test_layers() {
+        let css = [
+            ("background-image", "url(a.png), none, url(b.png)"),
+            ("background-repeat", "no-repeat, repeat-x"),
+            ("background-clip", "content-box"),
+            ("background-color", "red"),
+        ]
+        .into_iter()
+        .map(|(property, value)| (property.into(), value.to_string()))
+        .collect();
+        let layers = layers(&css, &LengthContext::default(), INITIAL_COLOR);
+
+        assert_eq!(
+            layers.iter().map(|layer| &layer.image).collect::<Vec<_>>(),
+            [
+                &Image::Url("a.png".into()),
+                &Image::Url("b.png".into()),
+                &Image::Color([1., 0., 0., 1.]),
+            ]
+        );
+        assert_eq!(layers[0].repeat, [Repeat::NoRepeat; 2]);
+        assert_eq!(layers[1].repeat, [Repeat::NoRepeat; 2]);
+        assert!(layers
+            .iter()
+            .all(|layer| layer.clip == BackgroundClip::ContentBox));
+
+        let layers = super::layers(&HashMap::new(), &LengthContext::default(), INITIAL_COLOR);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].image, Image::Color([0., 0., 0., 1.]));
+    }
+}
@@ -0,0 +1,646 @@
+use super::color::INITIAL_COLOR;
+use super::get_color;
+use super::length::{Length, LengthContext, LengthPercentage};
+use crate::cascade::{box_sides, SIDES};
+use crate::parser::{
+    self,
+    tokenizer::{self, Token},
+};
+use cairo::Context;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderStyle {
+    #[default]
+    None,
+    Hidden,
+    Solid,
+    Dashed,
+    Dotted,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
+}
+
+// Percentages are of the border box, which is only known once rendering
+#[derive(Debug, Clone, PartialEq)]
+pub enum Radius {
+    Px(f64),
+    Percent(f64),
+    // Math functions mixing lengths and percentages, e.g. `calc(50% - 4px)`
+    Calc(LengthPercentage),
+}
+
+// Top, right, bottom and left, in the same order as margin and padding. Radii are horizontal
+// and vertical, clockwise from the top left corner
+#[derive(Debug, Clone, Default)]
+pub struct Border {
+    pub width: [i32; 4],
+    pub style: [BorderStyle; 4],
+    pub color: [[f64; 4]; 4],
+    pub radius: [[Radius; 2]; 4],
+}
+
+pub(crate) const CORNERS: [&str; 4] = ["top-left", "top-right", "bottom-right", "bottom-left"];
+
+impl Default for Radius {
+    fn default() -> Self {
+        Radius::Px(0.)
+    }
+}
+
+impl Radius {
+    fn parse(value: &str, context: &LengthContext) -> Option<Self> {
+        match Length::parse(value)? {
+            Length::Percent(percent) => Some(Radius::Percent(percent)),
+            length if length.is_percent() => {
+                Some(Radius::Calc(LengthPercentage::new(length, context)))
+            }
+            length => length.resolve(context, None).map(Radius::Px),
+        }
+    }
+
+    pub fn resolve(&self, basis: f64) -> f64 {
+        match self {
+            Radius::Px(px) => *px,
+            Radius::Percent(percent) => percent * basis / 100.,
+            Radius::Calc(length) => length.resolve(basis),
+        }
+    }
+}
+
+impl BorderStyle {
+    fn parse(value: &str) -> Option<Self> {
+        let style = match value.to_ascii_lowercase().as_str() {
+            "none" => BorderStyle::None,
+            "hidden" => BorderStyle::Hidden,
+            "solid" => BorderStyle::Solid,
+            "dashed" => BorderStyle::Dashed,
+            "dotted" => BorderStyle::Dotted,
+            "double" => BorderStyle::Double,
+            "groove" => BorderStyle::Groove,
+            "ridge" => BorderStyle::Ridge,
+            "inset" => BorderStyle::Inset,
+            "outset" => BorderStyle::Outset,
+            _ => return None,
+        };
+
+        Some(style)
+    }
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#border-width
+fn get_width(value: &str, context: &LengthContext) -> Option<f64> {
+    match value.to_ascii_lowercase().as_str() {
+        "thin" => Some(1.),
+        "medium" => Some(3.),
+        "thick" => Some(5.),
+        _ => match Length::parse(value)? {
+            length if length.is_percent() => None,
+            length => length.resolve(context, None),
+        },
+    }
+}
+
+// Splits a `border` or `border-top` shorthand into its width, style and color, filling in
+// the initial values of the ones left out
+pub(crate) fn split_shorthand(value: &str) -> Option<[String; 3]> {
+    let values = parser::split_values(value);
+    if values.is_empty() || values.len() > 3 {
+        return None;
+    }
+
+    let mut split = [None, None, None];
+    for value in values {
+        let index = match value {
+            _ if BorderStyle::parse(value).is_some() => 1,
+            _ if get_width(value, &LengthContext::default()).is_some_and(|w| w >= 0.) => 0,
+            _ if get_color(value, INITIAL_COLOR).is_some() => 2,
+            _ => return None,
+        };
+        if split[index].replace(value).is_some() {
+            return None;
+        }
+    }
+
+    let [width, style, color] = split;
+    Some([
+        width.unwrap_or("medium").to_string(),
+        style.unwrap_or("none").to_string(),
+        color.unwrap_or("currentcolor").to_string(),
+    ])
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#border-radius
+// Splits `border-radius` into the horizontal and vertical radius of each corner, clockwise
+// from the top left, e.g. `10px 5% / 20px` into `10px 20px`, `5% 20px`, `10px 20px`, `5% 20px`
+pub(crate) fn split_radius(value: &str) -> Option<[String; 4]> {
+    let mut depth = 0;
+    let slash = tokenizer::tokenize(value).into_iter().find(|t| {
+        match t.token {
+            Token::Function(_) | Token::OpenParen => depth += 1,
+            Token::CloseParen => depth -= 1,
+            _ => {}
+        }
+        depth == 0 && t.token == Token::Delim('/')
+    });
+    let (horizontal, vertical) = match slash {
+        Some(slash) => (&value[..slash.span.start], Some(&value[slash.span.end..])),
+        None => (value, None),
+    };
+    let horizontal = box_sides(&parser::split_values(horizontal))?;
+    let vertical = match vertical {
+        Some(vertical) => box_sides(&parser::split_values(vertical))?,
+        None => horizontal,
+    };
+
+    Some([0, 1, 2, 3].map(|i| match horizontal[i] == vertical[i] {
+        true => horizontal[i].to_string(),
+        false => format!("{} {}", horizontal[i], vertical[i]),
+    }))
+}
+
+fn valid_radius(value: &str) -> bool {
+    let values = parser::split_values(value);
+    (1..=2).contains(&values.len())
+        && values.iter().all(|v| {
+            Radius::parse(v, &LengthContext::default()).is_some()
+                && Length::parse(v).is_some_and(|length| !length.is_negative())
+        })
+}
+
+// Returns None for properties that are not border related
+pub(super) fn validate(property: &str, value: &str) -> Option<Result<(), &'static str>> {
+    let property = property.strip_prefix("border")?;
+    if property == "-radius" {
+        let valid = split_radius(value).is_some_and(|radii| radii.iter().all(|r| valid_radius(r)));
+        return Some(valid.then_some(()).ok_or("Expected one to four radii"));
+    }
+    if CORNERS.iter().any(|corner| {
+        property
+            .strip_prefix('-')
+            .and_then(|p| p.strip_prefix(corner))
+            == Some("-radius")
+    }) {
+        return Some(
+            valid_radius(value)
+                .then_some(())
+                .ok_or("Expected one or two radii"),
+        );
+    }
+    // Per side longhands take a single value, the shorthands one for each side
+    let (property, sides) = match SIDES
+        .iter()
+        .find_map(|side| property.strip_prefix(&format!("-{side}")))
+    {
+        Some(property) => (property, 1..=1),
+        None => (property, 1..=4),
+    };
+
+    let valid = |value: &str| match property {
+        "-width" => get_width(value, &LengthContext::default()).is_some_and(|w| w >= 0.),
+        "-style" => BorderStyle::parse(value).is_some(),
+        "-color" => get_color(value, INITIAL_COLOR).is_some(),
+        _ => false,
+    };
+
+    let result = match property {
+        "" => split_shorthand(value)
+            .map(|_| ())
+            .ok_or("Expected a border width, style and color"),
+        "-width" | "-style" | "-color" => {
+            let values = parser::split_values(value);
+            match sides.contains(&values.len()) && values.iter().all(|v| valid(v)) {
+                true => Ok(()),
+                false => Err(match (property, sides.end()) {
+                    ("-width", 1) => "Expected a border width",
+                    ("-style", 1) => "Expected a border style",
+                    (_, 1) => "Expected a color",
+                    ("-width", _) => "Expected one to four border widths",
+                    ("-style", _) => "Expected one to four border styles",
+                    _ => "Expected one to four colors",
+                }),
+            }
+        }
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+impl Border {
+    pub(crate) fn new(
+        css: &HashMap<Box<str>, String>,
+        context: &LengthContext,
+        current_color: [f64; 4],
+    ) -> Self {
+        let get_property =
+            |side: &str, property: &str| css.get(&*format!("border-{side}-{property}"));
+
+        let style = SIDES.map(|side| {
+            get_property(side, "style")
+                .and_then(|s| BorderStyle::parse(s))
+                .unwrap_or_default()
+        });
+        let color = SIDES.map(|side| {
+            get_property(side, "color")
+                .and_then(|s| get_color(s, current_color))
+                .unwrap_or(current_color)
+        });
+
+        // Widths are snapped to whole pixels, and sides without a style have none
+        let mut width = [0; 4];
+        for (i, side) in SIDES.iter().enumerate() {
+            let px = get_property(side, "width")
+                .and_then(|s| get_width(s, context))
+                .unwrap_or(3.)
+                .max(0.);
+            width[i] = match style[i] {
+                BorderStyle::None | BorderStyle::Hidden => 0,
+                _ if px > 0. && px < 1. => 1,
+                _ => px.floor() as i32,
+            };
+        }
+
+        let radius = CORNERS.map(|corner| {
+            let values = css
+                .get(&*format!("border-{corner}-radius"))
+                .map(|s| parser::split_values(s))
+                .unwrap_or_default();
+            let radius = values
+                .iter()
+                .map(|v| Radius::parse(v, context))
+                .collect::<Option<Vec<_>>>();
+            match radius.as_deref() {
+                Some([radius]) => [radius.clone(), radius.clone()],
+                Some([horizontal, vertical]) => [horizontal.clone(), vertical.clone()],
+                _ => Default::default(),
+            }
+        });
+
+        Self {
+            width,
+            style,
+            color,
+            radius,
+        }
+    }
+
+    // https://www.w3.org/TR/css-backgrounds-3/#corner-overlap
+    // Resolves the horizontal and vertical radius of each corner of a border box, scaling
+    // all of them down when adjacent ones would overlap
+    pub fn radii(&self, width: f64, height: f64) -> [[f64; 2]; 4] {
+        let radii = self
+            .radius
+            .each_ref()
+            .map(|[x, y]| [x.resolve(width).max(0.), y.resolve(height).max(0.)]);
+        let [top_left, top_right, bottom_right, bottom_left] = radii;
+
+        let factor = [
+            width / (top_left[0] + top_right[0]),
+            height / (top_right[1] + bottom_right[1]),
+            width / (bottom_right[0] + bottom_left[0]),
+            height / (bottom_left[1] + top_left[1]),
+        ]
+        .into_iter()
+        .fold(1., f64::min);
+
+        radii.map(|radius| radius.map(|r| r * factor))
+    }
+
+    // The border box at `x` and `y` inset by a fraction of each side's width, with its radii
+    // shrunk to match. A fraction of 1 gives the padding box
+    pub(crate) fn inset(
+        &self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        fraction: f64,
+    ) -> (f64, f64, f64, f64, [[f64; 2]; 4]) {
+        inset_rectangle(
+            (x, y, width, height, self.radii(width, height)),
+            self.width.map(|w| f64::from(w) * fraction),
+        )
+    }
+
+    // Draws each side inside of the border box at `x` and `y`, following its rounded corners
+    pub(crate) fn draw(
+        &self,
+        context: &Context,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<(), cairo::Error> {
+        let inset = |fraction: f64| self.inset(x, y, width, height, fraction);
+        let path = |fraction: f64| {
+            let (x, y, width, height, radii) = inset(fraction);
+            rounded_rectangle(context, x, y, width, height, radii);
+        };
+
+        // Sides meet diagonally at the corners
+        let (outer, inner) = (inset(0.), inset(1.));
+        let trapezoids = [
+            [
+                (outer.0, outer.1),
+                (outer.0 + outer.2, outer.1),
+                (inner.0 + inner.2, inner.1),
+                (inner.0, inner.1),
+            ],
+            [
+                (outer.0 + outer.2, outer.1),
+                (outer.0 + outer.2, outer.1 + outer.3),
+                (inner.0 + inner.2, inner.1 + inner.3),
+                (inner.0 + inner.2, inner.1),
+            ],
+            [
+                (outer.0 + outer.2, outer.1 + outer.3),
+                (outer.0, outer.1 + outer.3),
+                (inner.0, inner.1 + inner.3),
+                (inner.0 + inner.2, inner.1 + inner.3),
+            ],
+            [
+                (outer.0, outer.1 + outer.3),
+                (outer.0, outer.1),
+                (inner.0, inner.1),
+                (inner.0, inner.1 + inner.3),
+            ],
+        ];
+
+        for (side, trapezoid) in trapezoids.iter().enumerate() {
+            let side_width = f64::from(self.width[side]);
+            if side_width == 0. {
+                continue;
+            }
+
+            context.save()?;
+            context.new_path();
+            for &(x, y) in trapezoid {
+                context.line_to(x, y);
+            }
+            context.close_path();
+            context.clip();
+            context.set_fill_rule(cairo::FillRule::EvenOdd);
+            path(0.);
+            path(1.);
+            context.clip();
+
+            // Top and left sides are the ones in shadow for `inset` and `groove`
+            let color = self.color[side];
+            let dark = [color[0] / 2., color[1] / 2., color[2] / 2., color[3]];
+            let (shadow, light) = match side {
+                0 | 3 => (dark, color),
+                _ => (color, dark),
+            };
+            let band = |from: f64, to: f64, color: [f64; 4]| {
+                context.set_source_rgba(color[0], color[1], color[2], color[3]);
+                path(from);
+                path(to);
+                context.fill()
+            };
+
+            match self.style[side] {
+                BorderStyle::None | BorderStyle::Hidden => {}
+                BorderStyle::Solid => band(0., 1., color)?,
+                BorderStyle::Double => {
+                    band(0., 1. / 3., color)?;
+                    band(2. / 3., 1., color)?;
+                }
+                BorderStyle::Inset => band(0., 1., shadow)?,
+                BorderStyle::Outset => band(0., 1., light)?,
+                BorderStyle::Groove => {
+                    band(0., 0.5, shadow)?;
+                    band(0.5, 1., light)?;
+                }
+                BorderStyle::Ridge => {
+                    band(0., 0.5, light)?;
+                    band(0.5, 1., shadow)?;
+                }
+                // Stroked along the middle of the border, clipped to this side
+                style @ (BorderStyle::Dashed | BorderStyle::Dotted) => {
+                    context.set_source_rgba(color[0], color[1], color[2], color[3]);
+                    context.set_line_width(side_width);
+                    match style {
+                        BorderStyle::Dotted => {
+                            context.set_line_cap(cairo::LineCap::Round);
+                            context.set_dash(&[0., side_width * 2.], side_width / 2.);
+                        }
+                        _ => {
+                            context.set_line_cap(cairo::LineCap::Butt);
+                            context.set_dash(&[side_width * 2., side_width], 0.);
+                        }
+                    }
+                    path(0.5);
+                    context.stroke()?;
+                }
+            }
+            context.restore()?;
+        }
+
+        Ok(())
+    }
+}
+
+// Insets a rounded rectangle by the top, right, bottom and left widths, shrinking its radii
+// along with it
+pub(crate) fn inset_rectangle(
+    (x, y, width, height, radii): (f64, f64, f64, f64, [[f64; 2]; 4]),
+    [top, right, bottom, left]: [f64; 4],
+) -> (f64, f64, f64, f64, [[f64; 2]; 4]) {
+    let [top_left, top_right, bottom_right, bottom_left] = radii;
+    (
+        x + left,
+        y + top,
+        width - left - right,
+        height - top - bottom,
+        [
+            [top_left[0] - left, top_left[1] - top],
+            [top_right[0] - right, top_right[1] - top],
+            [bottom_right[0] - right, bottom_right[1] - bottom],
+            [bottom_left[0] - left, bottom_left[1] - bottom],
+        ]
+        .map(|radius| radius.map(|r| r.max(0.))),
+    )
+}
+
+// Adds a rectangle with elliptical corners to the path, radii go clockwise from the top left
+pub(crate) fn rounded_rectangle(
+    context: &Context,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    radii: [[f64; 2]; 4],
+) {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    let [top_left, top_right, bottom_right, bottom_left] = radii;
+    // Each corner with the direction its ellipse's center is in and the angle its arc starts at
+    let corners = [
+        (x, y, top_left, (1., 1.), PI),
+        (x + width, y, top_right, (-1., 1.), -FRAC_PI_2),
+        (x + width, y + height, bottom_right, (-1., -1.), 0.),
+        (x, y + height, bottom_left, (1., -1.), FRAC_PI_2),
+    ];
+
+    context.new_sub_path();
+    for (corner_x, corner_y, [rx, ry], (dx, dy), angle) in corners {
+        if rx <= 0. || ry <= 0. {
+            context.line_to(corner_x, corner_y);
+            continue;
+        }
+        let matrix = context.matrix();
+        context.translate(corner_x + dx * rx, corner_y + dy * ry);
+        context.scale(rx, ry);
+        context.arc(0., 0., 1., angle, angle + FRAC_PI_2);
+        context.set_matrix(matrix);
+    }
+    context.close_path();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_shorthand() {
+        assert_eq!(
+            split_shorthand("1px solid red"),
+            Some(["1px".into(), "solid".into(), "red".into()])
+        );
+        assert_eq!(
+            split_shorthand("rgb(0 0 0) dashed"),
+            Some(["medium".into(), "dashed".into(), "rgb(0 0 0)".into()])
+        );
+        assert_eq!(
+            split_shorthand("thick"),
+            Some(["thick".into(), "none".into(), "currentcolor".into()])
+        );
+        assert_eq!(split_shorthand("1px 2px"), None);
+        assert_eq!(split_shorthand("solid dotted"), None);
+        assert_eq!(split_shorthand("-1px solid"), None);
+        assert_eq!(split_shorthand("1px solid red blue"), None);
+        assert_eq!(split_shorthand("wavy"), None);
+
+        assert_eq!(validate("border-width", "1px 2px"), Some(Ok(())));
+        assert_eq!(
+            validate("border-top-width", "1px 2px"),
+            Some(Err("Expected a border width"))
+        );
+        assert_eq!(
+            validate("border-left-style", "solid solid"),
+            Some(Err("Expected a border style"))
+        );
+        assert_eq!(validate("border-right-color", "red"), Some(Ok(())));
+        assert_eq!(validate("border-top", "1px solid"), Some(Ok(())));
+    }
+
+    #[test]
+    fn test_border() {
+        let css = [
+            ("border-top-width", "thin"),
+            ("border-top-style", "solid"),
+            ("border-right-width", "0.5px"),
+            ("border-right-style", "dashed"),
+            ("border-right-color", "red"),
+            ("border-bottom-width", "2.7px"),
+            ("border-bottom-style", "none"),
+            ("border-left-width", "1em"),
+            ("border-left-style", "double"),
+        ]
+        .iter()
+        .map(|(k, v)| ((*k).into(), v.to_string()))
+        .collect();
+        let context = LengthContext {
+            font_size: 10.,
+            ..Default::default()
+        };
+
+        let border = Border::new(&css, &context, [0., 0., 1., 1.]);
+        assert_eq!(border.width, [1, 1, 0, 10]);
+        assert_eq!(
+            border.style,
+            [
+                BorderStyle::Solid,
+                BorderStyle::Dashed,
+                BorderStyle::None,
+                BorderStyle::Double
+            ]
+        );
+        assert_eq!(border.color[0], [0., 0., 1., 1.]);
+        assert_eq!(border.color[1], [1., 0., 0., 1.]);
+    }
+
+    #[test]
+    fn test_split_radius() {
+        assert_eq!(
+            split_radius("10px"),
+            Some(["10px".into(), "10px".into(), "10px".into(), "10px".into()])
+        );
+        assert_eq!(
+            split_radius("10px 5% / 20px"),
+            Some([
+                "10px 20px".into(),
+                "5% 20px".into(),
+                "10px 20px".into(),
+                "5% 20px".into()
+            ])
+        );
+        assert_eq!(
+            split_radius("1px 2px 3px/calc(8px / 2)"),
+            Some([
+                "1px calc(8px / 2)".into(),
+                "2px calc(8px / 2)".into(),
+                "3px calc(8px / 2)".into(),
+                "2px calc(8px / 2)".into()
+            ])
+        );
+        assert_eq!(split_radius("1px 2px 3px 4px 5px"), None);
+        assert_eq!(split_radius("1px /"), None);
+    }
+
+    #[test]
+    fn test_radii() {
+        let border = Border {
+            radius: [
+                [Radius::Px(10.), Radius::Px(10.)],
+                [Radius::Percent(50.), Radius::Percent(25.)],
+                [Radius::Px(0.), Radius::Px(0.)],
+                [Radius::Px(-5.), Radius::Px(20.)],
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            border.radii(100., 40.),
+            [[10., 10.], [50., 10.], [0., 0.], [0., 20.]]
+        );
+
+        // The left side is 15px tall but its radii add up to 30px
+        assert_eq!(
+            border.radii(100., 15.),
+            [[5., 5.], [25., 1.875], [0., 0.], [0., 10.]]
+        );
+
+        // Math mixing lengths and percentages is resolved against the box
+        let css = [
+            ("border-top-left-radius", "calc(50% - 4px)"),
+            ("border-top-right-radius", "calc(10% + 1em) 2px"),
+        ]
+        .into_iter()
+        .map(|(property, value)| (property.into(), value.to_string()))
+        .collect();
+        let context = LengthContext {
+            font_size: 10.,
+            ..Default::default()
+        };
+        let border = Border::new(&css, &context, [0., 0., 0., 1.]);
+        assert_eq!(
+            border.radii(100., 40.),
+            [[46., 16.], [20., 2.], [0., 0.], [0., 0.]]
+        );
+        assert_eq!(validate("border-radius", "calc(50% - 4px)"), Some(Ok(())));
+    }
+}
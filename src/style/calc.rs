@@ -0,0 +1,290 @@
+use super::length::{Length, LengthContext};
+use crate::parser::tokenizer::{self, Spanned, Token};
+
+// https://www.w3.org/TR/css-values-4/#math
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Calc {
+    Number(f64),
+    Length(Length),
+    Sum(Box<Calc>, Box<Calc>),
+    Difference(Box<Calc>, Box<Calc>),
+    Product(Box<Calc>, Box<Calc>),
+    Quotient(Box<Calc>, Box<Calc>),
+    Min(Vec<Calc>),
+    Max(Vec<Calc>),
+    Clamp(Box<[Calc; 3]>),
+}
+
+// Percentages count as lengths, they are only resolved once the basis is known
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Number,
+    Length,
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    // Identifiers standing for numbers, e.g. the channels of relative colors
+    keywords: &'a [(&'a str, f64)],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // Returns whether there was any whitespace
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek() == Some(&Token::Whitespace) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    // `+` and `-` have to be surrounded by whitespace, `1px -2px` is two values
+    fn sum(&mut self) -> Option<(Calc, Kind)> {
+        let (mut left, kind) = self.product()?;
+        loop {
+            let start = self.pos;
+            let whitespace = self.skip_whitespace();
+            let operator = match self.peek() {
+                Some(Token::Delim(c @ ('+' | '-'))) if whitespace => *c,
+                _ => {
+                    self.pos = start;
+                    return Some((left, kind));
+                }
+            };
+            self.pos += 1;
+            if !self.skip_whitespace() {
+                return None;
+            }
+
+            let (right, right_kind) = self.product()?;
+            if right_kind != kind {
+                return None;
+            }
+            left = match operator {
+                '+' => Calc::Sum(Box::new(left), Box::new(right)),
+                _ => Calc::Difference(Box::new(left), Box::new(right)),
+            };
+        }
+    }
+
+    // At least one side of a product, and the right side of a quotient, has to be a number
+    fn product(&mut self) -> Option<(Calc, Kind)> {
+        let (mut left, mut kind) = self.value()?;
+        loop {
+            let start = self.pos;
+            self.skip_whitespace();
+            let operator = match self.peek() {
+                Some(Token::Delim(c @ ('*' | '/'))) => *c,
+                _ => {
+                    self.pos = start;
+                    return Some((left, kind));
+                }
+            };
+            self.pos += 1;
+            self.skip_whitespace();
+
+            let (right, right_kind) = self.value()?;
+            (left, kind) = match (operator, kind, right_kind) {
+                ('*', Kind::Length, Kind::Length) | ('/', _, Kind::Length) => return None,
+                ('*', Kind::Number, kind) | ('*', kind, Kind::Number) => {
+                    (Calc::Product(Box::new(left), Box::new(right)), kind)
+                }
+                (_, kind, _) => (Calc::Quotient(Box::new(left), Box::new(right)), kind),
+            };
+        }
+    }
+
+    fn value(&mut self) -> Option<(Calc, Kind)> {
+        match self.next()? {
+            Token::Number(number) => Some((Calc::Number(number), Kind::Number)),
+            Token::Ident(ident) => self
+                .keywords
+                .iter()
+                .find(|(name, _)| ident.eq_ignore_ascii_case(name))
+                .map(|(_, number)| (Calc::Number(*number), Kind::Number)),
+            Token::Function(name) => self.function(&name.to_ascii_lowercase()),
+            Token::OpenParen => {
+                self.skip_whitespace();
+                let value = self.sum()?;
+                self.skip_whitespace();
+                match self.next()? {
+                    Token::CloseParen => Some(value),
+                    _ => None,
+                }
+            }
+            token => Some((Calc::Length(Length::from_token(&token)?), Kind::Length)),
+        }
+    }
+
+    fn function(&mut self, name: &str) -> Option<(Calc, Kind)> {
+        if !matches!(name, "calc" | "min" | "max" | "clamp") {
+            return None;
+        }
+
+        let mut args = Vec::new();
+        let mut kind = None;
+        loop {
+            self.skip_whitespace();
+            let (arg, arg_kind) = self.sum()?;
+            if *kind.get_or_insert(arg_kind) != arg_kind {
+                return None;
+            }
+            args.push(arg);
+            self.skip_whitespace();
+
+            match self.next()? {
+                Token::Comma if name != "calc" => {}
+                Token::CloseParen => break,
+                _ => return None,
+            }
+        }
+
+        let calc = match (name, args.len()) {
+            ("calc", _) => args.pop()?,
+            ("min", _) => Calc::Min(args),
+            ("max", _) => Calc::Max(args),
+            ("clamp", 3) => Calc::Clamp(Box::new(args.try_into().ok()?)),
+            _ => return None,
+        };
+
+        Some((calc, kind?))
+    }
+}
+
+impl Calc {
+    // Only math functions resolving to a length are accepted, e.g. `calc(100% - 2em)`
+    pub fn parse(value: &str) -> Option<Self> {
+        match Self::parse_tokens(&tokenizer::tokenize(value.trim()), &[])? {
+            (calc, Kind::Length) => Some(calc),
+            _ => None,
+        }
+    }
+
+    // Evaluates a math function resolving to a plain number, e.g. `calc(l * 0.8)`
+    pub fn parse_number(tokens: &[Spanned], keywords: &[(&str, f64)]) -> Option<f64> {
+        match Self::parse_tokens(tokens, keywords)? {
+            (calc, Kind::Number) => calc.resolve(&LengthContext::default(), None),
+            _ => None,
+        }
+    }
+
+    fn parse_tokens(tokens: &[Spanned], keywords: &[(&str, f64)]) -> Option<(Self, Kind)> {
+        let mut parser = Parser {
+            tokens: tokens.iter().map(|t| t.token.clone()).collect(),
+            pos: 0,
+            keywords,
+        };
+
+        if !matches!(parser.peek(), Some(Token::Function(_))) {
+            return None;
+        }
+        let calc = parser.value()?;
+        match parser.pos == parser.tokens.len() {
+            true => Some(calc),
+            false => None,
+        }
+    }
+
+    pub fn contains_percent(&self) -> bool {
+        match self {
+            Calc::Number(_) => false,
+            Calc::Length(length) => length.is_percent(),
+            Calc::Sum(a, b)
+            | Calc::Difference(a, b)
+            | Calc::Product(a, b)
+            | Calc::Quotient(a, b) => a.contains_percent() || b.contains_percent(),
+            Calc::Min(args) | Calc::Max(args) => args.iter().any(Calc::contains_percent),
+            Calc::Clamp(args) => args.iter().any(Calc::contains_percent),
+        }
+    }
+
+    pub fn resolve(&self, context: &LengthContext, percent_basis: Option<f64>) -> Option<f64> {
+        let resolve = |calc: &Calc| calc.resolve(context, percent_basis);
+
+        let value = match self {
+            Calc::Number(number) => *number,
+            Calc::Length(length) => length.resolve(context, percent_basis)?,
+            Calc::Sum(a, b) => resolve(a)? + resolve(b)?,
+            Calc::Difference(a, b) => resolve(a)? - resolve(b)?,
+            Calc::Product(a, b) => resolve(a)? * resolve(b)?,
+            Calc::Quotient(a, b) => resolve(a)? / resolve(b)?,
+            Calc::Min(args) => args.iter().map(resolve).reduce(|a, b| Some(a?.min(b?)))??,
+            Calc::Max(args) => args.iter().map(resolve).reduce(|a, b| Some(a?.max(b?)))??,
+            // The minimum wins over the maximum when they overlap
+            Calc::Clamp(args) => {
+                let [min, value, max] = &**args;
+                resolve(value)?.min(resolve(max)?).max(resolve(min)?)
+            }
+        };
+
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert!(Calc::parse("calc(100% - 2em)").is_some());
+        assert!(Calc::parse("calc((1px + 2px) * 3 / 2)").is_some());
+        assert!(Calc::parse("clamp(10px, 5vw, 40px)").is_some());
+        assert!(Calc::parse("min(1em, max(2px, 3%))").is_some());
+        assert!(Calc::parse("calc(2 * 1px)").is_some());
+
+        assert!(Calc::parse("calc(1px)").is_some());
+        assert!(Calc::parse("calc(1)").is_none());
+        assert!(Calc::parse("calc(1px-2px)").is_none());
+        assert!(Calc::parse("calc(1px -2px)").is_none());
+        assert!(Calc::parse("calc(1px +2px)").is_none());
+        assert!(Calc::parse("calc(1px + 2)").is_none());
+        assert!(Calc::parse("calc(1px * 2px)").is_none());
+        assert!(Calc::parse("calc(1px / 2px)").is_none());
+        assert!(Calc::parse("calc(1px, 2px)").is_none());
+        assert!(Calc::parse("clamp(1px, 2px)").is_none());
+        assert!(Calc::parse("calc(1px) 2px").is_none());
+        assert!(Calc::parse("calc(1px").is_none());
+        assert!(Calc::parse("foo(1px)").is_none());
+        assert!(Calc::parse("(1px)").is_none());
+
+        let number = |value| Calc::parse_number(&tokenizer::tokenize(value), &[("r", 255.)]);
+        assert_eq!(number("calc(r / 2 + 1)"), Some(128.5));
+        assert_eq!(number("max(R, 300)"), Some(300.));
+        assert_eq!(number("calc(g)"), None);
+        assert_eq!(number("calc(1px)"), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let context = LengthContext {
+            font_size: 10.,
+            root_font_size: 16.,
+            viewport: Some((400., 300.)),
+        };
+        let resolve = |value| Calc::parse(value).unwrap().resolve(&context, Some(200.));
+
+        assert_eq!(resolve("calc(100% - 2em)"), Some(180.));
+        assert_eq!(resolve("calc((1px + 2px) * 3 / 2)"), Some(4.5));
+        assert_eq!(resolve("calc(1px + 2px * 3)"), Some(7.));
+        assert_eq!(resolve("clamp(10px, 5vw, 40px)"), Some(20.));
+        assert_eq!(resolve("clamp(10px, 50vw, 40px)"), Some(40.));
+        assert_eq!(resolve("clamp(50px, 5vw, 40px)"), Some(50.));
+        assert_eq!(resolve("min(1em, max(2px, 3%))"), Some(6.));
+
+        let calc = Calc::parse("calc(100% - 2em)").unwrap();
+        assert!(calc.contains_percent());
+        assert_eq!(calc.resolve(&context, None), None);
+    }
+}
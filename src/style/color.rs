@@ -0,0 +1,950 @@
+use super::calc::Calc;
+use crate::parser::tokenizer::{self, Spanned, Token};
+use std::f64::consts::PI;
+
+// Initial value of `color`, also what `currentColor` is within `color` itself
+pub(super) const INITIAL_COLOR: [f64; 4] = [0., 0., 0., 1.];
+
+const KAPPA: f64 = 24389. / 27.;
+const EPSILON: f64 = 216. / 24389.;
+const D50: [f64; 3] = [0.3457 / 0.3585, 1., (1. - 0.3457 - 0.3585) / 0.3585];
+
+// https://www.w3.org/TR/css-color-4/#color-conversion-code
+const SRGB_TO_XYZ: [[f64; 3]; 3] = [
+    [506752. / 1228815., 87881. / 245763., 12673. / 70218.],
+    [87098. / 409605., 175762. / 245763., 12673. / 175545.],
+    [7918. / 409605., 87881. / 737289., 1001167. / 1053270.],
+];
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+    [12831. / 3959., -329. / 214., -1974. / 3959.],
+    [-851781. / 878810., 1648619. / 878810., 36519. / 878810.],
+    [705. / 12673., -2585. / 12673., 705. / 667.],
+];
+const D65_TO_D50: [[f64; 3]; 3] = [
+    [
+        1.0479297925449969,
+        0.022946870601609652,
+        -0.05019226628920524,
+    ],
+    [
+        0.02962780877005599,
+        0.9904344267538799,
+        -0.017073799063418826,
+    ],
+    [
+        -0.009243040646204504,
+        0.015055191490298152,
+        0.7518742814281371,
+    ],
+];
+const D50_TO_D65: [[f64; 3]; 3] = [
+    [0.955473421488075, -0.02309845494876471, 0.06325924320057072],
+    [
+        -0.0283697093338637,
+        1.0099953980813041,
+        0.021041441191917323,
+    ],
+    [
+        0.012314014864481998,
+        -0.020507649298898964,
+        1.330365926242124,
+    ],
+];
+
+// https://bottosson.github.io/posts/oklab/
+const SRGB_TO_LMS: [[f64; 3]; 3] = [
+    [0.4122214708, 0.5363325363, 0.0514459929],
+    [0.2119034982, 0.6806995451, 0.1073969566],
+    [0.0883024619, 0.2817188376, 0.6299787005],
+];
+const LMS_TO_SRGB: [[f64; 3]; 3] = [
+    [4.0767416621, -3.3077115913, 0.2309699292],
+    [-1.2684380046, 2.6097574011, -0.3413193965],
+    [-0.0041960863, -0.7034186147, 1.707614701],
+];
+const LMS_TO_OKLAB: [[f64; 3]; 3] = [
+    [0.2104542553, 0.793617785, -0.0040720468],
+    [1.9779984951, -2.428592205, 0.4505937099],
+    [0.0259040371, 0.7827717662, -0.808675766],
+];
+const OKLAB_TO_LMS: [[f64; 3]; 3] = [
+    [1., 0.3963377774, 0.2158037573],
+    [1., -0.1055613458, -0.0638541728],
+    [1., -0.0894841775, -1.291485548],
+];
+
+// https://www.w3.org/TR/css-color-4/#named-colors, sorted for binary search
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+// `current_color` is what `currentColor` resolves to, the element's `color`
+pub(super) fn get_color(color: &str, current_color: [f64; 4]) -> Option<[f64; 4]> {
+    let color = color.trim().replace('"', "");
+
+    if let Some(hex) = color.strip_prefix('#') {
+        return get_hex(hex);
+    }
+    let tokens = tokenizer::tokenize(&color);
+    if let Some(Token::Function(name)) = tokens.first().map(|t| &t.token) {
+        return get_function(
+            &name.to_ascii_lowercase(),
+            &color,
+            &tokens[1..],
+            current_color,
+        );
+    }
+
+    let name = color.to_ascii_lowercase();
+    match name.as_str() {
+        "transparent" => return Some([0., 0., 0., 0.]),
+        "currentcolor" => return Some(current_color),
+        _ => {}
+    }
+    let index = NAMED_COLORS
+        .binary_search_by(|(named, _)| named.cmp(&name.as_str()))
+        .ok()?;
+    let rgb = NAMED_COLORS[index].1;
+    Some([
+        (rgb >> 16) as f64 / 255.,
+        (rgb >> 8 & 0xff) as f64 / 255.,
+        (rgb & 0xff) as f64 / 255.,
+        1.,
+    ])
+}
+
+// `#rgb` and `#rgba` are short for `#rrggbb` and `#rrggbbaa`
+fn get_hex(hex: &str) -> Option<[f64; 4]> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits = match hex.len() {
+        3 | 4 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex.to_string(),
+        _ => return None,
+    };
+
+    let channel = |i: usize| {
+        digits
+            .get(i * 2..i * 2 + 2)
+            .map_or(Some(255), |c| u8::from_str_radix(c, 16).ok())
+            .map(|c| c as f64 / 255.)
+    };
+    Some([channel(0)?, channel(1)?, channel(2)?, channel(3)?])
+}
+
+// Color spaces of the functional notations and `color-mix()`, channels are in the units
+// relative colors name them in, e.g. 0 to 255 for sRGB and 0 to 100 for HSL lightness
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Space {
+    Srgb,
+    SrgbLinear,
+    Hsl,
+    Hwb,
+    Lab,
+    Lch,
+    Oklab,
+    Oklch,
+    XyzD50,
+    XyzD65,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HueInterpolation {
+    Shorter,
+    Longer,
+    Increasing,
+    Decreasing,
+}
+
+impl Space {
+    fn parse(name: &str) -> Option<Self> {
+        let space = match name {
+            "srgb" => Space::Srgb,
+            "srgb-linear" => Space::SrgbLinear,
+            "hsl" => Space::Hsl,
+            "hwb" => Space::Hwb,
+            "lab" => Space::Lab,
+            "lch" => Space::Lch,
+            "oklab" => Space::Oklab,
+            "oklch" => Space::Oklch,
+            "xyz-d50" => Space::XyzD50,
+            "xyz" | "xyz-d65" => Space::XyzD65,
+            _ => return None,
+        };
+
+        Some(space)
+    }
+
+    fn channels(self) -> [&'static str; 3] {
+        match self {
+            Space::Srgb | Space::SrgbLinear => ["r", "g", "b"],
+            Space::Hsl => ["h", "s", "l"],
+            Space::Hwb => ["h", "w", "b"],
+            Space::Lab | Space::Oklab => ["l", "a", "b"],
+            Space::Lch | Space::Oklch => ["l", "c", "h"],
+            Space::XyzD50 | Space::XyzD65 => ["x", "y", "z"],
+        }
+    }
+
+    // What 100% of each channel is, None for hues
+    fn percent_max(self) -> [Option<f64>; 3] {
+        match self {
+            Space::Srgb => [Some(255.); 3],
+            Space::SrgbLinear | Space::XyzD50 | Space::XyzD65 => [Some(1.); 3],
+            Space::Hsl | Space::Hwb => [None, Some(100.), Some(100.)],
+            Space::Lab => [Some(100.), Some(125.), Some(125.)],
+            Space::Lch => [Some(100.), Some(150.), None],
+            Space::Oklab => [Some(1.), Some(0.4), Some(0.4)],
+            Space::Oklch => [Some(1.), Some(0.4), None],
+        }
+    }
+
+    fn hue(self) -> Option<usize> {
+        self.percent_max().iter().position(Option::is_none)
+    }
+
+    // Achromatic colors have no meaningful hue, so interpolation uses the other color's
+    fn is_powerless(self, channels: [f64; 3]) -> bool {
+        match self {
+            Space::Hsl => channels[1] < 1e-6,
+            Space::Hwb => channels[1] + channels[2] > 100. - 1e-6,
+            Space::Lch => channels[1] < 1e-2,
+            Space::Oklch => channels[1] < 1e-4,
+            _ => false,
+        }
+    }
+
+    fn convert(self, rgb: [f64; 3]) -> [f64; 3] {
+        let xyz = || multiply(SRGB_TO_XYZ, rgb.map(linear));
+        let oklab = || {
+            let lms = multiply(SRGB_TO_LMS, rgb.map(linear));
+            multiply(LMS_TO_OKLAB, lms.map(f64::cbrt))
+        };
+
+        match self {
+            Space::Srgb => rgb.map(|c| c * 255.),
+            Space::SrgbLinear => rgb.map(linear),
+            Space::Hsl => rgb_to_hsl(rgb),
+            Space::Hwb => {
+                let [hue, ..] = rgb_to_hsl(rgb);
+                let (min, max) = (
+                    rgb[0].min(rgb[1]).min(rgb[2]),
+                    rgb[0].max(rgb[1]).max(rgb[2]),
+                );
+                [hue, min * 100., (1. - max) * 100.]
+            }
+            Space::Lab => xyz_to_lab(multiply(D65_TO_D50, xyz())),
+            Space::Lch => to_polar(xyz_to_lab(multiply(D65_TO_D50, xyz()))),
+            Space::Oklab => oklab(),
+            Space::Oklch => to_polar(oklab()),
+            Space::XyzD50 => multiply(D65_TO_D50, xyz()),
+            Space::XyzD65 => xyz(),
+        }
+    }
+
+    fn to_rgb(self, channels: [f64; 3]) -> [f64; 3] {
+        let from_xyz = |xyz| multiply(XYZ_TO_SRGB, xyz).map(gamma);
+        let from_oklab = |oklab| {
+            let lms = multiply(OKLAB_TO_LMS, oklab);
+            multiply(LMS_TO_SRGB, lms.map(|c: f64| c.powi(3))).map(gamma)
+        };
+
+        match self {
+            Space::Srgb => channels.map(|c| c / 255.),
+            Space::SrgbLinear => channels.map(gamma),
+            Space::Hsl => hsl_to_rgb(channels[0], channels[1] / 100., channels[2] / 100.),
+            Space::Hwb => hwb_to_rgb(channels[0], channels[1] / 100., channels[2] / 100.),
+            Space::Lab => from_xyz(multiply(D50_TO_D65, lab_to_xyz(channels))),
+            Space::Lch => from_xyz(multiply(D50_TO_D65, lab_to_xyz(from_polar(channels)))),
+            Space::Oklab => from_oklab(channels),
+            Space::Oklch => from_oklab(from_polar(channels)),
+            Space::XyzD50 => from_xyz(multiply(D50_TO_D65, channels)),
+            Space::XyzD65 => from_xyz(channels),
+        }
+    }
+}
+
+impl HueInterpolation {
+    // https://www.w3.org/TR/css-color-4/#hue-interpolation
+    fn adjust(self, a: &mut f64, b: &mut f64) {
+        let difference = *b - *a;
+        match self {
+            HueInterpolation::Shorter if difference > 180. => *a += 360.,
+            HueInterpolation::Shorter if difference < -180. => *b += 360.,
+            HueInterpolation::Longer if 0. < difference && difference < 180. => *a += 360.,
+            HueInterpolation::Longer if -180. < difference && difference <= 0. => *b += 360.,
+            HueInterpolation::Increasing if difference < 0. => *b += 360.,
+            HueInterpolation::Decreasing if difference > 0. => *a += 360.,
+            _ => {}
+        }
+    }
+}
+
+// Groups tokens into component values, a function with its arguments being a single one
+fn components(tokens: &[Spanned]) -> Vec<&[Spanned]> {
+    let mut components = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, t) in tokens.iter().enumerate() {
+        if depth == 0 {
+            start = i;
+        }
+        match t.token {
+            Token::Function(_) | Token::OpenParen => depth += 1,
+            Token::CloseParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0 && t.token != Token::Whitespace {
+            components.push(&tokens[start..=i]);
+        }
+    }
+    if depth > 0 {
+        components.push(&tokens[start..]);
+    }
+
+    components
+}
+
+fn is_token(component: &[Spanned], token: &Token) -> bool {
+    matches!(component, [t] if t.token == *token)
+}
+
+fn text<'a>(source: &'a str, component: &[Spanned]) -> Option<&'a str> {
+    let (first, last) = (component.first()?, component.last()?);
+    source.get(first.span.start..last.span.end)
+}
+
+// https://www.w3.org/TR/css-color-4/#color-syntax
+// The comma separated legacy syntax is only accepted by `rgb()` and `hsl()`
+fn get_function(
+    name: &str,
+    source: &str,
+    tokens: &[Spanned],
+    current_color: [f64; 4],
+) -> Option<[f64; 4]> {
+    let (close, args) = tokens.split_last()?;
+    if close.token != Token::CloseParen {
+        return None;
+    }
+    let args = components(args);
+    if name == "color-mix" {
+        return color_mix(source, &args, current_color);
+    }
+
+    let space = match name {
+        "rgb" | "rgba" => Space::Srgb,
+        "hsl" | "hsla" => Space::Hsl,
+        "hwb" => Space::Hwb,
+        "lab" => Space::Lab,
+        "lch" => Space::Lch,
+        "oklab" => Space::Oklab,
+        "oklch" => Space::Oklch,
+        _ => return None,
+    };
+
+    // https://www.w3.org/TR/css-color-5/#relative-colors
+    // The channels of the origin color, e.g. `r` in `rgb(from red r g b / 50%)`, can be used
+    // in place of numbers
+    let (keywords, args) = match args.as_slice() {
+        [[from], origin, args @ ..] if matches!(&from.token, Token::Ident(from) if from.eq_ignore_ascii_case("from")) =>
+        {
+            let origin = get_color(text(source, origin)?, current_color)?;
+            let channels = space.convert([origin[0], origin[1], origin[2]]);
+            let keywords = space
+                .channels()
+                .into_iter()
+                .zip(channels)
+                .chain([("alpha", origin[3])])
+                .collect::<Vec<_>>();
+            (keywords, args)
+        }
+        args => (Vec::new(), args),
+    };
+
+    let (channels, alpha) = match args.iter().any(|c| is_token(c, &Token::Comma)) {
+        true => {
+            if !matches!(name, "rgb" | "rgba" | "hsl" | "hsla")
+                || !keywords.is_empty()
+                || args
+                    .iter()
+                    .skip(1)
+                    .step_by(2)
+                    .any(|c| !is_token(c, &Token::Comma))
+            {
+                return None;
+            }
+            let values = args.iter().step_by(2).copied().collect::<Vec<_>>();
+            match values.len() {
+                3 => (values, None),
+                4 => (values[..3].to_vec(), Some(values[3])),
+                _ => return None,
+            }
+        }
+        false => match args.iter().position(|c| is_token(c, &Token::Delim('/'))) {
+            Some(i) => match args[i + 1..] {
+                [alpha] => (args[..i].to_vec(), Some(alpha)),
+                _ => return None,
+            },
+            None => (args.to_vec(), None),
+        },
+    };
+    let channels = <[&[Spanned]; 3]>::try_from(channels).ok()?;
+
+    let mut values = [0.; 3];
+    for ((value, channel), percent_max) in values.iter_mut().zip(channels).zip(space.percent_max())
+    {
+        *value = match percent_max {
+            Some(percent_max) => number(channel, percent_max, &keywords)?,
+            None => hue(channel, &keywords)?,
+        };
+    }
+    // Relative colors keep the origin's alpha unless given another one
+    let alpha = match alpha {
+        Some(alpha) => number(alpha, 1., &keywords)?,
+        None => keywords.get(3).map_or(1., |(_, alpha)| *alpha),
+    };
+
+    let [red, green, blue] = space.to_rgb(values);
+    // Colors outside of the sRGB gamut are clipped
+    Some([red, green, blue, alpha].map(|channel| channel.clamp(0., 1.)))
+}
+
+// https://www.w3.org/TR/css-color-5/#color-mix
+// Channels other than the hue are interpolated premultiplied by alpha
+fn color_mix(source: &str, args: &[&[Spanned]], current_color: [f64; 4]) -> Option<[f64; 4]> {
+    let groups = args
+        .split(|c| is_token(c, &Token::Comma))
+        .collect::<Vec<_>>();
+    let [method, first, second] = <[&[&[Spanned]]; 3]>::try_from(groups).ok()?;
+
+    let idents = method
+        .iter()
+        .map(|c| match c {
+            [Spanned {
+                token: Token::Ident(ident),
+                ..
+            }] => Some(ident.to_ascii_lowercase()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let idents = idents.iter().map(String::as_str).collect::<Vec<_>>();
+    let (space, interpolation) = match idents.as_slice() {
+        ["in", space] => (Space::parse(space)?, HueInterpolation::Shorter),
+        ["in", space, interpolation, "hue"] => {
+            let interpolation = match *interpolation {
+                "shorter" => HueInterpolation::Shorter,
+                "longer" => HueInterpolation::Longer,
+                "increasing" => HueInterpolation::Increasing,
+                "decreasing" => HueInterpolation::Decreasing,
+                _ => return None,
+            };
+            let space = Space::parse(space)?;
+            space.hue()?;
+            (space, interpolation)
+        }
+        _ => return None,
+    };
+
+    let mix_color = |group: &[&[Spanned]]| {
+        let percentage = |c: &[Spanned]| match c {
+            [Spanned {
+                token: Token::Percentage(percentage),
+                ..
+            }] => Some(*percentage),
+            _ => None,
+        };
+        let (color, percentage) = match *group {
+            [color] => (color, None),
+            [color, p] | [p, color] if percentage(p).is_some() => (color, percentage(p)),
+            _ => return None,
+        };
+        Some((get_color(text(source, color)?, current_color)?, percentage))
+    };
+    let (first, p1) = mix_color(first)?;
+    let (second, p2) = mix_color(second)?;
+
+    let (p1, p2) = match (p1, p2) {
+        (None, None) => (50., 50.),
+        (Some(p1), None) => (p1, 100. - p1),
+        (None, Some(p2)) => (100. - p2, p2),
+        (Some(p1), Some(p2)) => (p1, p2),
+    };
+    if !(0. ..=100.).contains(&p1) || !(0. ..=100.).contains(&p2) || p1 + p2 == 0. {
+        return None;
+    }
+    // Percentages adding up to less than 100% make the result more transparent
+    let alpha_multiplier = ((p1 + p2) / 100.).min(1.);
+    let (w1, w2) = (p1 / (p1 + p2), p2 / (p1 + p2));
+
+    let mut a = space.convert([first[0], first[1], first[2]]);
+    let mut b = space.convert([second[0], second[1], second[2]]);
+    let hue = space.hue();
+    if let Some(h) = hue {
+        match (space.is_powerless(a), space.is_powerless(b)) {
+            (true, false) => a[h] = b[h],
+            (false, true) => b[h] = a[h],
+            _ => {}
+        }
+        let (mut hue_a, mut hue_b) = (a[h], b[h]);
+        interpolation.adjust(&mut hue_a, &mut hue_b);
+        (a[h], b[h]) = (hue_a, hue_b);
+    }
+
+    let alpha = first[3] * w1 + second[3] * w2;
+    let mut mixed = [0.; 3];
+    for (i, channel) in mixed.iter_mut().enumerate() {
+        *channel = match Some(i) == hue {
+            true => (a[i] * w1 + b[i] * w2).rem_euclid(360.),
+            false if alpha > 0. => (a[i] * first[3] * w1 + b[i] * second[3] * w2) / alpha,
+            false => a[i] * w1 + b[i] * w2,
+        };
+    }
+
+    let [red, green, blue] = space.to_rgb(mixed);
+    Some([red, green, blue, alpha * alpha_multiplier].map(|channel| channel.clamp(0., 1.)))
+}
+
+// Percentages are scaled so that 100% is `percent_max`, `none` is treated as zero
+fn number(component: &[Spanned], percent_max: f64, keywords: &[(&str, f64)]) -> Option<f64> {
+    let token = match component {
+        [t] => &t.token,
+        _ => return Calc::parse_number(component, keywords),
+    };
+
+    match token {
+        Token::Number(number) => Some(*number),
+        Token::Percentage(percent) => Some(percent / 100. * percent_max),
+        Token::Ident(ident) if ident.eq_ignore_ascii_case("none") => Some(0.),
+        Token::Ident(ident) => keyword(ident, keywords),
+        _ => None,
+    }
+}
+
+// Returns the hue in degrees
+fn hue(component: &[Spanned], keywords: &[(&str, f64)]) -> Option<f64> {
+    let token = match component {
+        [t] => &t.token,
+        _ => return Calc::parse_number(component, keywords).map(|hue| hue.rem_euclid(360.)),
+    };
+
+    let degrees = match token {
+        Token::Number(degrees) => *degrees,
+        Token::Dimension(value, unit) => match unit.to_ascii_lowercase().as_str() {
+            "deg" => *value,
+            "rad" => value * 180. / PI,
+            "grad" => value * 0.9,
+            "turn" => value * 360.,
+            _ => return None,
+        },
+        Token::Ident(ident) if ident.eq_ignore_ascii_case("none") => 0.,
+        Token::Ident(ident) => keyword(ident, keywords)?,
+        _ => return None,
+    };
+
+    Some(degrees.rem_euclid(360.))
+}
+
+fn keyword(ident: &str, keywords: &[(&str, f64)]) -> Option<f64> {
+    keywords
+        .iter()
+        .find(|(name, _)| ident.eq_ignore_ascii_case(name))
+        .map(|(_, value)| *value)
+}
+
+// https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> [f64; 3] {
+    let a = saturation * lightness.min(1. - lightness);
+    [0., 8., 4.].map(|n| {
+        let k = (n + hue / 30.) % 12.;
+        lightness - a * (k - 3.).min(9. - k).clamp(-1., 1.)
+    })
+}
+
+// https://www.w3.org/TR/css-color-4/#rgb-to-hsl
+fn rgb_to_hsl([red, green, blue]: [f64; 3]) -> [f64; 3] {
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let lightness = (max + min) / 2.;
+    let delta = max - min;
+
+    let (mut hue, mut saturation) = (0., 0.);
+    if delta != 0. {
+        if lightness != 0. && lightness != 1. {
+            saturation = (max - lightness) / lightness.min(1. - lightness);
+        }
+        hue = match max {
+            max if max == red => (green - blue) / delta + if green < blue { 6. } else { 0. },
+            max if max == green => (blue - red) / delta + 2.,
+            _ => (red - green) / delta + 4.,
+        } * 60.;
+    }
+
+    [hue, saturation * 100., lightness * 100.]
+}
+
+// https://www.w3.org/TR/css-color-4/#hwb-to-rgb
+fn hwb_to_rgb(hue: f64, whiteness: f64, blackness: f64) -> [f64; 3] {
+    if whiteness + blackness >= 1. {
+        return [whiteness / (whiteness + blackness); 3];
+    }
+    hsl_to_rgb(hue, 1., 0.5).map(|channel| channel * (1. - whiteness - blackness) + whiteness)
+}
+
+// CIE Lab is relative to D50, it is adapted to the D65 white point of sRGB separately
+fn lab_to_xyz([lightness, a, b]: [f64; 3]) -> [f64; 3] {
+    let f1 = (lightness + 16.) / 116.;
+    let f0 = a / 500. + f1;
+    let f2 = f1 - b / 200.;
+    let inverse = |f: f64| match f.powi(3) > EPSILON {
+        true => f.powi(3),
+        false => (116. * f - 16.) / KAPPA,
+    };
+    let y = match lightness > KAPPA * EPSILON {
+        true => f1.powi(3),
+        false => lightness / KAPPA,
+    };
+
+    [inverse(f0) * D50[0], y, inverse(f2) * D50[2]]
+}
+
+fn xyz_to_lab(xyz: [f64; 3]) -> [f64; 3] {
+    let [x, y, z] = [xyz[0] / D50[0], xyz[1], xyz[2] / D50[2]].map(|v| match v > EPSILON {
+        true => v.cbrt(),
+        false => (KAPPA * v + 16.) / 116.,
+    });
+
+    [116. * y - 16., 500. * (x - y), 200. * (y - z)]
+}
+
+fn to_polar([lightness, a, b]: [f64; 3]) -> [f64; 3] {
+    [
+        lightness,
+        a.hypot(b),
+        b.atan2(a).to_degrees().rem_euclid(360.),
+    ]
+}
+
+fn from_polar([lightness, chroma, hue]: [f64; 3]) -> [f64; 3] {
+    let (sin, cos) = hue.to_radians().sin_cos();
+    [lightness, chroma.max(0.) * cos, chroma.max(0.) * sin]
+}
+
+fn multiply(matrix: [[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row.iter().zip(vector).map(|(m, v)| m * v).sum())
+}
+
+// The sRGB transfer function, from linear light
+fn gamma(channel: f64) -> f64 {
+    match channel.abs() > 0.0031308 {
+        true => channel.signum() * (1.055 * channel.abs().powf(1. / 2.4) - 0.055),
+        false => 12.92 * channel,
+    }
+}
+
+fn linear(channel: f64) -> f64 {
+    match channel.abs() > 0.04045 {
+        true => channel.signum() * ((channel.abs() + 0.055) / 1.055).powf(2.4),
+        false => channel / 12.92,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(get_color("#ffffff", INITIAL_COLOR), Some([1., 1., 1., 1.]));
+        assert_eq!(get_color("#fff", INITIAL_COLOR), Some([1., 1., 1., 1.]));
+        assert_eq!(
+            get_color("#f0f8", INITIAL_COLOR),
+            Some([1., 0., 1., 136. / 255.])
+        );
+        assert_eq!(
+            get_color("#ff000080", INITIAL_COLOR),
+            Some([1., 0., 0., 128. / 255.])
+        );
+        assert_eq!(get_color("#ff", INITIAL_COLOR), None);
+        assert_eq!(get_color("#fffff", INITIAL_COLOR), None);
+        assert_eq!(get_color("#ggg", INITIAL_COLOR), None);
+    }
+
+    #[test]
+    fn test_named() {
+        assert_eq!(get_color("white", INITIAL_COLOR), Some([1., 1., 1., 1.]));
+        assert_eq!(
+            get_color("Green", INITIAL_COLOR),
+            Some([0., 128. / 255., 0., 1.])
+        );
+        assert_eq!(get_color("\"blue\"", INITIAL_COLOR), Some([0., 0., 1., 1.]));
+        assert_eq!(
+            get_color("rebeccapurple", INITIAL_COLOR),
+            Some([0.4, 0.2, 0.6, 1.])
+        );
+        assert_eq!(
+            get_color("transparent", INITIAL_COLOR),
+            Some([0., 0., 0., 0.])
+        );
+        assert_eq!(get_color("notacolor", INITIAL_COLOR), None);
+        assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    fn assert_color(color: &str, expected: [f64; 4]) {
+        let color = get_color(color, [1., 0., 0., 1.])
+            .unwrap_or_else(|| panic!("`{color}` should be valid"));
+        let close = color
+            .iter()
+            .zip(expected)
+            .all(|(a, b)| (a - b).abs() < 0.005);
+        assert!(close, "{color:?} != {expected:?}");
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_color("rgb(255, 0, 0)", [1., 0., 0., 1.]);
+        assert_color("rgba(0,0,0,0.5)", [0., 0., 0., 0.5]);
+        assert_color("rgb(100% 50% 0 / 50%)", [1., 0.5, 0., 0.5]);
+        assert_color("rgb(none 255 0)", [0., 1., 0., 1.]);
+        assert_color("hsl(120, 100%, 50%)", [0., 1., 0., 1.]);
+        assert_color("hsl(0.5turn 100% 25% / 0.2)", [0., 0.5, 0.5, 0.2]);
+        assert_color("hsla(240deg, 100%, 50%, 1)", [0., 0., 1., 1.]);
+        assert_color("hwb(0 0% 0%)", [1., 0., 0., 1.]);
+        assert_color("hwb(0 60% 60%)", [0.5, 0.5, 0.5, 1.]);
+        assert_color("lab(100 0 0)", [1., 1., 1., 1.]);
+        assert_color("lab(54.29 80.8 69.89)", [1., 0., 0., 1.]);
+        assert_color("lch(54.29 106.84 40.85)", [1., 0., 0., 1.]);
+        assert_color("oklab(1 0 0)", [1., 1., 1., 1.]);
+        assert_color("oklab(62.8% 0.2249 0.1258)", [1., 0., 0., 1.]);
+        assert_color("oklch(0.628 0.2577 29.23 / 0.5)", [1., 0., 0., 0.5]);
+        assert_color("oklch(0 0 0)", [0., 0., 0., 1.]);
+
+        assert_eq!(get_color("rgb(255 0)", INITIAL_COLOR), None);
+        assert_eq!(get_color("rgb(255, 0 0)", INITIAL_COLOR), None);
+        assert_eq!(get_color("rgb(255 0 0 / )", INITIAL_COLOR), None);
+        assert_eq!(get_color("hwb(0, 0%, 0%)", INITIAL_COLOR), None);
+        assert_eq!(get_color("lab(50 0 0", INITIAL_COLOR), None);
+        assert_eq!(get_color("foo(1 2 3)", INITIAL_COLOR), None);
+    }
+
+    #[test]
+    fn test_current_color() {
+        assert_color("currentColor", [1., 0., 0., 1.]);
+        assert_color("rgb(from currentcolor r g b / 50%)", [1., 0., 0., 0.5]);
+        assert_eq!(
+            get_color("currentColor", INITIAL_COLOR),
+            Some(INITIAL_COLOR)
+        );
+    }
+
+    #[test]
+    fn test_relative_colors() {
+        assert_color("rgb(from red r g b / 50%)", [1., 0., 0., 0.5]);
+        assert_color("rgb(from #00000080 r g b)", [0., 0., 0., 128. / 255.]);
+        assert_color("rgb(from red b g r)", [0., 0., 1., 1.]);
+        assert_color(
+            "rgb(from red calc(r / 2) g b / calc(alpha / 4))",
+            [0.5, 0., 0., 0.25],
+        );
+        assert_color("hsl(from red calc(h + 120) s l)", [0., 1., 0., 1.]);
+        assert_color(
+            "hsl(from hsl(0 100% 50%) h s calc(l - 25))",
+            [0.5, 0., 0., 1.],
+        );
+        assert_color("oklch(from red l c h)", [1., 0., 0., 1.]);
+        assert_color("lab(from white l 0 0)", [1., 1., 1., 1.]);
+
+        assert_eq!(get_color("rgb(from red r g)", INITIAL_COLOR), None);
+        assert_eq!(get_color("rgb(from red, r, g, b)", INITIAL_COLOR), None);
+        assert_eq!(get_color("rgb(from notacolor r g b)", INITIAL_COLOR), None);
+        assert_eq!(get_color("rgb(from red h s l)", INITIAL_COLOR), None);
+    }
+
+    #[test]
+    fn test_color_mix() {
+        assert_color("color-mix(in srgb, red, blue)", [0.5, 0., 0.5, 1.]);
+        assert_color("color-mix(in srgb, red 25%, blue)", [0.25, 0., 0.75, 1.]);
+        assert_color(
+            "color-mix(in srgb, 75% red, blue 25%)",
+            [0.75, 0., 0.25, 1.],
+        );
+        assert_color("color-mix(in srgb, red 20%, blue 20%)", [0.5, 0., 0.5, 0.4]);
+        assert_color("color-mix(in srgb, red, transparent)", [1., 0., 0., 0.5]);
+        assert_color("color-mix(in oklab, white 100%, black)", [1., 1., 1., 1.]);
+        assert_color("color-mix(in hsl, red, lime)", [1., 1., 0., 1.]);
+        assert_color("color-mix(in hsl longer hue, red, lime)", [0., 0., 1., 1.]);
+        assert_color("color-mix(in oklch, white, white)", [1., 1., 1., 1.]);
+        assert_color(
+            "color-mix(in srgb, currentColor, white)",
+            [1., 0.5, 0.5, 1.],
+        );
+        assert_color("color-mix(in lab, rgb(255 0 0) 50%, red)", [1., 0., 0., 1.]);
+
+        assert_eq!(get_color("color-mix(in srgb, red)", INITIAL_COLOR), None);
+        assert_eq!(get_color("color-mix(srgb, red, blue)", INITIAL_COLOR), None);
+        assert_eq!(
+            get_color("color-mix(in foo, red, blue)", INITIAL_COLOR),
+            None
+        );
+        assert_eq!(
+            get_color("color-mix(in srgb longer hue, red, blue)", INITIAL_COLOR),
+            None
+        );
+        assert_eq!(
+            get_color("color-mix(in srgb, red 0%, blue 0%)", INITIAL_COLOR),
+            None
+        );
+        assert_eq!(
+            get_color("color-mix(in srgb, red 150%, blue)", INITIAL_COLOR),
+            None
+        );
+    }
+}
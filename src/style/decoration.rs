@@ -0,0 +1,396 @@
+use super::color::INITIAL_COLOR;
+use super::get_color;
+use super::length::{Length, LengthContext};
+use crate::parser;
+use cairo::Context;
+use std::collections::HashMap;
+
+// The longhands `text-decoration` sets and their initial values
+pub(crate) const LONGHANDS: [(&str, &str); 4] = [
+    ("text-decoration-line", "none"),
+    ("text-decoration-style", "solid"),
+    ("text-decoration-color", "currentcolor"),
+    ("text-decoration-thickness", "auto"),
+];
+
+const LINES: [&str; 3] = ["underline", "overline", "line-through"];
+
+// https://www.w3.org/TR/css-text-decor-3/#text-decoration-style-property
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DecorationStyle {
+    #[default]
+    Solid,
+    Double,
+    Dotted,
+    Dashed,
+    Wavy,
+}
+
+impl DecorationStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "solid" => Some(Self::Solid),
+            "double" => Some(Self::Double),
+            "dotted" => Some(Self::Dotted),
+            "dashed" => Some(Self::Dashed),
+            "wavy" => Some(Self::Wavy),
+            _ => None,
+        }
+    }
+}
+
+// https://www.w3.org/TR/css-text-decor-3/#line-decoration
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextDecoration {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+    pub style: DecorationStyle,
+    pub color: [f64; 4],
+    // None uses the thickness the font asks for
+    pub thickness: Option<f64>,
+}
+
+// https://compat.spec.whatwg.org/#the-webkit-text-stroke
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextStroke {
+    pub width: f64,
+    pub color: [f64; 4],
+}
+
+// `none` or each of underline, overline and line-through at most once, in any order
+fn lines(value: &str) -> Option<[bool; 3]> {
+    let values = parser::split_values(value);
+    if let [none] = values[..] {
+        if none.eq_ignore_ascii_case("none") {
+            return Some([false; 3]);
+        }
+    }
+
+    let mut lines = [false; 3];
+    for value in values {
+        let index = LINES
+            .iter()
+            .position(|line| value.eq_ignore_ascii_case(line))?;
+        if std::mem::replace(&mut lines[index], true) {
+            return None;
+        }
+    }
+    lines.contains(&true).then_some(lines)
+}
+
+// `auto` and `from-font` both follow the font, percentages are of the font size
+fn thickness(value: &str, context: &LengthContext) -> Option<Option<f64>> {
+    match value.to_ascii_lowercase().as_str() {
+        "auto" | "from-font" => Some(None),
+        _ => Length::parse(value)
+            .filter(|length| !length.is_negative())?
+            .resolve(context, Some(context.font_size))
+            .map(Some),
+    }
+}
+
+fn stroke_width(value: &str, context: &LengthContext) -> Option<f64> {
+    match value.to_ascii_lowercase().as_str() {
+        "thin" => Some(1.),
+        "medium" => Some(3.),
+        "thick" => Some(5.),
+        _ => match Length::parse(value)? {
+            length if length.is_percent() || length.is_negative() => None,
+            length => length.resolve(context, None),
+        },
+    }
+}
+
+// Splits a `text-decoration` shorthand into its line, style, color and thickness, filling in
+// the initial values of the ones left out
+pub(crate) fn split_shorthand(value: &str) -> Option<[String; 4]> {
+    let context = LengthContext::default();
+    let mut line = Vec::new();
+    let mut split = [None, None, None];
+
+    for value in parser::split_values(value) {
+        let index = match value {
+            _ if LINES.iter().any(|l| value.eq_ignore_ascii_case(l)) => {
+                line.push(value);
+                continue;
+            }
+            _ if value.eq_ignore_ascii_case("none") && line.is_empty() => {
+                line.push(value);
+                continue;
+            }
+            _ if DecorationStyle::parse(value).is_some() => 0,
+            _ if thickness(value, &context).is_some() => 2,
+            _ if get_color(value, INITIAL_COLOR).is_some() => 1,
+            _ => return None,
+        };
+        if split[index].replace(value).is_some() {
+            return None;
+        }
+    }
+
+    let line = match line.is_empty() {
+        true => "none".to_string(),
+        false => line.join(" "),
+    };
+    lines(&line)?;
+    let [style, color, thickness] = split;
+    Some([
+        line,
+        style.unwrap_or("solid").to_string(),
+        color.unwrap_or("currentcolor").to_string(),
+        thickness.unwrap_or("auto").to_string(),
+    ])
+}
+
+// Splits a `-webkit-text-stroke` shorthand into its width and color
+pub(crate) fn split_stroke(value: &str) -> Option<[String; 2]> {
+    let context = LengthContext::default();
+    let mut split = [None, None];
+
+    for value in parser::split_values(value) {
+        let index = match value {
+            _ if stroke_width(value, &context).is_some() => 0,
+            _ if get_color(value, INITIAL_COLOR).is_some() => 1,
+            _ => return None,
+        };
+        if split[index].replace(value).is_some() {
+            return None;
+        }
+    }
+
+    let [width, color] = split;
+    if width.is_none() && color.is_none() {
+        return None;
+    }
+    Some([
+        width.unwrap_or("0").to_string(),
+        color.unwrap_or("currentcolor").to_string(),
+    ])
+}
+
+// Returns None for properties that are not decoration or stroke related
+pub(super) fn validate(property: &str, value: &str) -> Option<Result<(), &'static str>> {
+    let context = LengthContext::default();
+    let result = match property {
+        "text-decoration" => split_shorthand(value)
+            .map(|_| ())
+            .ok_or("Expected a decoration line, style, color and thickness"),
+        "text-decoration-line" => lines(value)
+            .map(|_| ())
+            .ok_or("Expected none or any of underline, overline and line-through"),
+        "text-decoration-style" => DecorationStyle::parse(value)
+            .map(|_| ())
+            .ok_or("Expected solid, double, dotted, dashed or wavy"),
+        "text-decoration-color" | "-webkit-text-stroke-color" => get_color(value, INITIAL_COLOR)
+            .map(|_| ())
+            .ok_or("Invalid color"),
+        "text-decoration-thickness" => thickness(value, &context)
+            .map(|_| ())
+            .ok_or("Expected auto, from-font or a length"),
+        "-webkit-text-stroke" => split_stroke(value)
+            .map(|_| ())
+            .ok_or("Expected a stroke width and color"),
+        "-webkit-text-stroke-width" => stroke_width(value, &context)
+            .map(|_| ())
+            .ok_or("Expected a length"),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+impl TextDecoration {
+    pub(crate) fn new(
+        css: &HashMap<Box<str>, String>,
+        context: &LengthContext,
+        current_color: [f64; 4],
+    ) -> Self {
+        let [underline, overline, line_through] = css
+            .get("text-decoration-line")
+            .and_then(|s| lines(s))
+            .unwrap_or_default();
+
+        Self {
+            underline,
+            overline,
+            line_through,
+            style: css
+                .get("text-decoration-style")
+                .and_then(|s| DecorationStyle::parse(s))
+                .unwrap_or_default(),
+            color: css
+                .get("text-decoration-color")
+                .and_then(|s| get_color(s, current_color))
+                .unwrap_or(current_color),
+            thickness: css
+                .get("text-decoration-thickness")
+                .and_then(|s| thickness(s, context))
+                .unwrap_or_default(),
+        }
+    }
+
+    // Draws a line centered on `y`, running `width` to the right of `x`, in the style's pattern
+    pub(crate) fn draw(
+        &self,
+        context: &Context,
+        x: f64,
+        y: f64,
+        width: f64,
+        thickness: f64,
+    ) -> Result<(), cairo::Error> {
+        if width <= 0. || thickness <= 0. {
+            return Ok(());
+        }
+
+        context.save()?;
+        context.set_line_width(thickness);
+        match self.style {
+            DecorationStyle::Solid | DecorationStyle::Dotted | DecorationStyle::Dashed => {
+                match self.style {
+                    DecorationStyle::Dotted => {
+                        context.set_line_cap(cairo::LineCap::Round);
+                        context.set_dash(&[0., thickness * 2.], 0.);
+                    }
+                    DecorationStyle::Dashed => context.set_dash(&[thickness * 3.], 0.),
+                    _ => {}
+                }
+                context.move_to(x, y);
+                context.line_to(x + width, y);
+            }
+            // Two lines as thick as a single one would be, with a gap as wide in between
+            DecorationStyle::Double => {
+                context.set_line_width(thickness / 2.);
+                for y in [y - thickness * 0.75, y + thickness * 0.75] {
+                    context.move_to(x, y);
+                    context.line_to(x + width, y);
+                }
+            }
+            // Half waves of curves swinging up and down by twice the thickness
+            DecorationStyle::Wavy => {
+                let half = (thickness * 2.).max(2.);
+                let amplitude = thickness * 1.5;
+                context.move_to(x, y);
+                let mut start = x;
+                let mut up = true;
+                while start < x + width {
+                    let peak = match up {
+                        true => y - amplitude,
+                        false => y + amplitude,
+                    };
+                    context.curve_to(
+                        start + half / 2.,
+                        peak,
+                        start + half / 2.,
+                        peak,
+                        start + half,
+                        y,
+                    );
+                    start += half;
+                    up = !up;
+                }
+            }
+        }
+        context.set_source_rgba(self.color[0], self.color[1], self.color[2], self.color[3]);
+        context.stroke()?;
+        context.restore()
+    }
+}
+
+impl TextStroke {
+    pub(crate) fn new(
+        css: &HashMap<Box<str>, String>,
+        context: &LengthContext,
+        current_color: [f64; 4],
+    ) -> Self {
+        Self {
+            width: css
+                .get("-webkit-text-stroke-width")
+                .and_then(|s| stroke_width(s, context))
+                .unwrap_or(0.),
+            color: css
+                .get("-webkit-text-stroke-color")
+                .and_then(|s| get_color(s, current_color))
+                .unwrap_or(current_color),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_shorthand() {
+        assert_eq!(
+            split_shorthand("underline"),
+            Some(["underline", "solid", "currentcolor", "auto"].map(String::from))
+        );
+        assert_eq!(
+            split_shorthand("red 2px line-through wavy underline"),
+            Some(["line-through underline", "wavy", "red", "2px"].map(String::from))
+        );
+        assert_eq!(
+            split_shorthand("none dotted"),
+            Some(["none", "dotted", "currentcolor", "auto"].map(String::from))
+        );
+        assert_eq!(split_shorthand("underline underline"), None);
+        assert_eq!(split_shorthand("none underline"), None);
+        assert_eq!(split_shorthand("underline solid dashed"), None);
+        assert_eq!(split_shorthand("underline foo"), None);
+
+        assert_eq!(
+            split_stroke("blue 2px"),
+            Some(["2px", "blue"].map(String::from))
+        );
+        assert_eq!(
+            split_stroke("thin"),
+            Some(["thin", "currentcolor"].map(String::from))
+        );
+        assert_eq!(split_stroke("1px 2px"), None);
+        assert_eq!(split_stroke("-1px"), None);
+    }
+
+    #[test]
+    fn test_new() {
+        let context = LengthContext {
+            font_size: 10.,
+            ..Default::default()
+        };
+        let css = [
+            ("text-decoration-line", "overline line-through"),
+            ("text-decoration-style", "Wavy"),
+            ("text-decoration-thickness", "20%"),
+            ("-webkit-text-stroke-width", "0.5em"),
+            ("-webkit-text-stroke-color", "blue"),
+        ]
+        .into_iter()
+        .map(|(property, value)| (property.into(), value.to_string()))
+        .collect();
+
+        assert_eq!(
+            TextDecoration::new(&css, &context, [1., 0., 0., 1.]),
+            TextDecoration {
+                underline: false,
+                overline: true,
+                line_through: true,
+                style: DecorationStyle::Wavy,
+                color: [1., 0., 0., 1.],
+                thickness: Some(2.),
+            }
+        );
+        assert_eq!(
+            TextStroke::new(&css, &context, [1., 0., 0., 1.]),
+            TextStroke {
+                width: 5.,
+                color: [0., 0., 1., 1.],
+            }
+        );
+        assert!(validate("text-decoration-line", "none blink")
+            .unwrap()
+            .is_err());
+        assert!(validate("text-decoration-thickness", "-1px")
+            .unwrap()
+            .is_err());
+    }
+}
@@ -1,102 +1,383 @@
-use super::get_color;
+use super::color::INITIAL_COLOR;
+use super::decoration::{TextDecoration, TextStroke};
+use super::font_face::{self, family_name, FontFace};
+use super::length::{Length, LengthContext};
+use super::shadow::Shadow;
+use super::text::{LineHeight, TextDirection, UnicodeBidi, WhiteSpace};
+use super::{get_color, validate_length};
+use crate::{parser, RenderOptions};
 use std::collections::HashMap;
 
+// Initial value of `font-size`, what `em`, `rem` and percentages in `font-size` resolve against
+pub(super) const DEFAULT_FONT_SIZE: f64 = 12.0;
+
+// Initial value of `font-weight`, what `bolder` and `lighter` are relative to
+const DEFAULT_FONT_WEIGHT: FontWeight = FontWeight(400.0);
+
+// https://www.w3.org/TR/css-fonts-4/#font-stretch-prop
+// The width keywords, their percentages and the closest Pango widths
+const STRETCHES: [(&str, f64, pango::Stretch); 9] = [
+    ("ultra-condensed", 50., pango::Stretch::UltraCondensed),
+    ("extra-condensed", 62.5, pango::Stretch::ExtraCondensed),
+    ("condensed", 75., pango::Stretch::Condensed),
+    ("semi-condensed", 87.5, pango::Stretch::SemiCondensed),
+    ("normal", 100., pango::Stretch::Normal),
+    ("semi-expanded", 112.5, pango::Stretch::SemiExpanded),
+    ("expanded", 125., pango::Stretch::Expanded),
+    ("extra-expanded", 150., pango::Stretch::ExtraExpanded),
+    ("ultra-expanded", 200., pango::Stretch::UltraExpanded),
+];
+
+// https://www.w3.org/TR/css-fonts-4/#font-weight-absolute-values
+// A weight from 1 to 1000, 400 being normal and 700 bold
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct FontWeight(f64);
+
+impl FontWeight {
+    pub fn new(weight: f64) -> Option<Self> {
+        (1.0..=1000.)
+            .contains(&weight)
+            .then_some(FontWeight(weight))
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+
+    // Pango takes any whole weight, the named ones have to be named to compare equal
+    pub fn to_pango(self) -> pango::Weight {
+        match self.0.round() as i32 {
+            100 => pango::Weight::Thin,
+            200 => pango::Weight::Ultralight,
+            300 => pango::Weight::Light,
+            350 => pango::Weight::Semilight,
+            380 => pango::Weight::Book,
+            400 => pango::Weight::Normal,
+            500 => pango::Weight::Medium,
+            600 => pango::Weight::Semibold,
+            700 => pango::Weight::Bold,
+            800 => pango::Weight::Ultrabold,
+            900 => pango::Weight::Heavy,
+            1000 => pango::Weight::Ultraheavy,
+            weight => pango::Weight::__Unknown(weight),
+        }
+    }
+}
+
+// https://www.w3.org/TR/css-fonts-4/#font-stretch-prop
+// A width as a percentage of the normal one
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct FontStretch(f64);
+
+impl FontStretch {
+    pub fn new(percentage: f64) -> Option<Self> {
+        (percentage >= 0. && percentage.is_finite()).then_some(FontStretch(percentage))
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+
+    // The closest of the keyword widths, the only ones Pango has
+    pub fn to_pango(self) -> pango::Stretch {
+        self.closest().2
+    }
+
+    fn closest(self) -> &'static (&'static str, f64, pango::Stretch) {
+        STRETCHES
+            .iter()
+            .min_by(|a, b| (a.1 - self.0).abs().total_cmp(&(b.1 - self.0).abs()))
+            .unwrap_or(&STRETCHES[4])
+    }
+}
+
+// https://www.w3.org/TR/css-fonts-4/#relative-weights
+fn weight(value: &str, parent: FontWeight) -> Option<FontWeight> {
+    let bolder = match value.trim().to_ascii_lowercase().as_str() {
+        "bolder" => true,
+        "lighter" => false,
+        value => return font_face::weight(value),
+    };
+
+    let parent = parent.get();
+    let weight = match (bolder, parent) {
+        (true, ..350.) => 400.,
+        (true, ..550.) => 700.,
+        (true, ..900.) => 900.,
+        (true, _) | (false, ..100.) => parent,
+        (false, ..550.) => 100.,
+        (false, ..750.) => 400.,
+        (false, _) => 700.,
+    };
+    FontWeight::new(weight)
+}
+
+fn stretch(value: &str) -> Option<FontStretch> {
+    let value = value.trim();
+    match STRETCHES
+        .iter()
+        .find(|(keyword, ..)| value.eq_ignore_ascii_case(keyword))
+    {
+        Some((_, percentage, _)) => FontStretch::new(*percentage),
+        None => FontStretch::new(value.strip_suffix('%')?.parse::<f64>().ok()?),
+    }
+}
+
+// https://www.w3.org/TR/css-fonts-4/#font-variation-settings-def
+// The axes of a variable font to set, e.g. `"wght" 650, "wdth" 80`. A later value for an axis
+// replaces an earlier one
+fn variations(value: &str) -> Option<Vec<(String, f64)>> {
+    if value.trim().eq_ignore_ascii_case("normal") {
+        return Some(Vec::new());
+    }
+
+    let mut variations: Vec<(String, f64)> = Vec::new();
+    for axis in parser::split_list(value) {
+        let [tag, value] = parser::split_values(axis)[..] else {
+            return None;
+        };
+        let tag = parser::unquote(tag).filter(|tag| tag.len() == 4 && tag.is_ascii())?;
+        let value = value.parse::<f64>().ok()?;
+
+        variations.retain(|(other, _)| *other != tag);
+        variations.push((tag, value));
+    }
+    Some(variations)
+}
+
+// https://www.w3.org/TR/css-fonts-4/#generic-font-families
+// The fontconfig families each generic family is looked up as, in order
+const GENERIC_FAMILIES: [(&str, &[&str]); 5] = [
+    ("serif", &["serif"]),
+    ("sans-serif", &["sans-serif"]),
+    ("monospace", &["monospace"]),
+    ("system-ui", &["system-ui", "sans-serif"]),
+    ("emoji", &["emoji"]),
+];
+
+// https://www.w3.org/TR/css-fonts-4/#font-family-prop
+// The families of a fallback list, e.g. `"Inter", Helvetica Neue, sans-serif`. Generic families
+// are keywords, so `"serif"` is a family named serif rather than the generic one
+fn families(value: &str) -> Option<Vec<String>> {
+    let mut families = Vec::new();
+    for family in parser::split_list(value) {
+        match GENERIC_FAMILIES
+            .iter()
+            .find(|(generic, _)| family.eq_ignore_ascii_case(generic))
+        {
+            Some((_, fontconfig)) => families.extend(fontconfig.iter().map(|f| f.to_string())),
+            None => families.push(family_name(family)?),
+        }
+    }
+    Some(families)
+}
+
 #[derive(Debug, Clone)]
 pub struct Font {
     pub color: [f64; 4],
     pub size: f64,
-    pub family: Box<str>,
-    pub style: cairo::FontSlant,
-    pub weight: cairo::FontWeight,
+    pub weight: FontWeight,
+    pub stretch: FontStretch,
+    // Values of the variable font axes, by tag
+    pub variations: Vec<(String, f64)>,
+    // The family, style, weight, width, variations and size to shape the content with
+    pub description: pango::FontDescription,
     pub text_align: Box<str>,
     pub letter_spacing: f64,
+    pub word_spacing: f64,
+    pub white_space: WhiteSpace,
+    pub line_height: LineHeight,
+    pub direction: TextDirection,
+    pub unicode_bidi: UnicodeBidi,
+    // The `@font-face` rules for the families in the description
+    pub faces: Vec<FontFace>,
+    pub text_decoration: TextDecoration,
+    // Drawn in order from the top
+    pub text_shadow: Vec<Shadow>,
+    pub text_stroke: TextStroke,
 }
+
+// Returns None for properties that are not font related
+pub(super) fn validate(property: &str, value: &str) -> Option<Result<(), &'static str>> {
+    let result = match property {
+        "font-size" => validate_length(value, false, true),
+        "letter-spacing" | "word-spacing" if value.trim().eq_ignore_ascii_case("normal") => Ok(()),
+        "letter-spacing" | "word-spacing" => validate_length(value, true, false),
+        "color" => get_color(value, INITIAL_COLOR)
+            .map(|_| ())
+            .ok_or("Invalid color"),
+        "font-family" => families(value)
+            .map(|_| ())
+            .ok_or("Expected a list of family names"),
+        "font-style" => match value {
+            "normal" | "italic" | "oblique" => Ok(()),
+            _ => Err("Expected normal, italic or oblique"),
+        },
+        "font-weight" => weight(value, DEFAULT_FONT_WEIGHT)
+            .map(|_| ())
+            .ok_or("Expected normal, bold, bolder, lighter or a number from 1 to 1000"),
+        "font-stretch" => stretch(value)
+            .map(|_| ())
+            .ok_or("Expected a width keyword or a percentage"),
+        "font-variation-settings" => variations(value)
+            .map(|_| ())
+            .ok_or("Expected normal or a list of four letter axis tags and numbers"),
+        "text-align" => match value {
+            "start" | "end" | "left" | "center" | "right" => Ok(()),
+            _ => Err("Expected start, end, left, center or right"),
+        },
+        _ => return None,
+    };
+
+    Some(result)
+}
+
 impl Font {
     pub fn new(
         css: &HashMap<Box<str>, String>,
-        all_selector: Option<&HashMap<Box<str>, String>>,
+        options: &RenderOptions,
+        faces: &[FontFace],
     ) -> Self {
-        let get_property = |property: &str, default: f64| {
-            css.get(property)
-                .and_then(|s| {
-                    if s.ends_with("px") {
-                        Some(s.replace("px", ""))
-                    } else {
-                        None
-                    }
-                })
-                .and_then(|s| s.parse::<f64>().ok())
-                .or_else(|| {
-                    all_selector
-                        .as_ref()?
-                        .get(property)
-                        .and_then(|s| {
-                            if s.ends_with("px") {
-                                Some(s.replace("px", ""))
-                            } else {
-                                None
-                            }
-                        })
-                        .and_then(|s| s.parse::<f64>().ok())
-                })
-                .unwrap_or(default)
+        // Every element is a root, so its font-size is relative to the initial one
+        let mut context = LengthContext {
+            font_size: DEFAULT_FONT_SIZE,
+            root_font_size: DEFAULT_FONT_SIZE,
+            viewport: options.viewport,
         };
 
-        let size = get_property("font-size", 12.0);
+        let size = css
+            .get("font-size")
+            .and_then(|s| Length::parse(s))
+            .and_then(|s| s.resolve(&context, Some(DEFAULT_FONT_SIZE)))
+            .map(|size| size.max(0.))
+            .unwrap_or(DEFAULT_FONT_SIZE);
+        context.font_size = size;
+        context.root_font_size = size;
 
         let color = css
             .get("color")
-            .or_else(|| all_selector.as_ref()?.get("color"))
-            .map(|color| get_color(color))
-            .unwrap_or([0., 0., 0., 1.]);
+            .and_then(|color| get_color(color, INITIAL_COLOR))
+            .unwrap_or(INITIAL_COLOR);
+
+        // `normal` is no extra spacing, and is what anything that isn't a length falls back to
+        let spacing = |property: &str| {
+            css.get(property)
+                .and_then(|s| Length::parse(s))
+                .and_then(|s| s.resolve(&context, None))
+                .unwrap_or(0.0)
+        };
+        let letter_spacing = spacing("letter-spacing");
+        let word_spacing = spacing("word-spacing");
 
-        let family = css
+        let white_space = css
+            .get("white-space")
+            .and_then(|s| WhiteSpace::parse(s))
+            .unwrap_or_default();
+
+        let line_height = css
+            .get("line-height")
+            .and_then(|s| LineHeight::parse(s, &context))
+            .unwrap_or_default();
+
+        let families = css
             .get("font-family")
-            .or_else(|| all_selector.as_ref()?.get("font-family"))
-            .map(|s| s.trim().replace('\"', ""))
-            .unwrap_or_else(|| "Arial".to_string())
-            .into();
+            .and_then(|s| families(s))
+            .unwrap_or_else(|| vec!["Arial".to_string()]);
 
-        let letter_spacing = css
-            .get("letter-spacing")
-            .or_else(|| all_selector.as_ref()?.get("font-family"))
-            .map(|s| s.trim().replace("px", "").parse::<f64>().unwrap_or(0.0))
-            .unwrap_or_else(|| 0.0);
-
-        let style = css
-            .get("font-style")
-            .or_else(|| all_selector.as_ref()?.get("font-style"))
-            .map(|s| match s.as_str() {
-                "italic" => cairo::FontSlant::Italic,
-                "oblique" => cairo::FontSlant::Oblique,
-                _ => cairo::FontSlant::Normal,
-            })
-            .unwrap_or(cairo::FontSlant::Normal);
+        let style = match css.get("font-style").map(|s| s.as_str()) {
+            Some("italic") => pango::Style::Italic,
+            Some("oblique") => pango::Style::Oblique,
+            _ => pango::Style::Normal,
+        };
 
+        // Every element is a root, so `bolder` and `lighter` are relative to the initial weight
         let weight = css
             .get("font-weight")
-            .or_else(|| all_selector.as_ref()?.get("font-weight"))
-            .map(|s| match s.as_str() {
-                "bold" => cairo::FontWeight::Bold,
-                _ => cairo::FontWeight::Normal,
+            .and_then(|s| weight(s, DEFAULT_FONT_WEIGHT))
+            .unwrap_or(DEFAULT_FONT_WEIGHT);
+
+        let stretch = css
+            .get("font-stretch")
+            .and_then(|s| stretch(s))
+            .unwrap_or(FontStretch(100.));
+
+        let variations = css
+            .get("font-variation-settings")
+            .and_then(|s| variations(s))
+            .unwrap_or_default();
+
+        let faces = faces
+            .iter()
+            .filter(|face| {
+                families
+                    .iter()
+                    .any(|family| face.family.eq_ignore_ascii_case(family))
             })
-            .unwrap_or(cairo::FontWeight::Normal);
+            .cloned()
+            .collect();
+
+        let mut description = pango::FontDescription::new();
+        // Fontconfig tries the families in order for each character
+        description.set_family(&families.join(","));
+        description.set_style(style);
+        description.set_weight(weight.to_pango());
+        description.set_stretch(stretch.to_pango());
+
+        // Fontconfig only knows the keyword widths, so variable fonts get the exact one as an axis
+        let mut axes = variations
+            .iter()
+            .map(|(tag, value)| format!("{tag}={value}"))
+            .collect::<Vec<_>>();
+        if stretch.closest().1 != stretch.get() && !variations.iter().any(|(tag, _)| tag == "wdth")
+        {
+            axes.insert(0, format!("wdth={}", stretch.get()));
+        }
+        if !axes.is_empty() {
+            description.set_variations(Some(&axes.join(",")));
+        }
+        description.set_absolute_size(size * f64::from(pango::SCALE));
+
+        let direction = css
+            .get("direction")
+            .and_then(|s| TextDirection::parse(s))
+            .unwrap_or_default();
+
+        let unicode_bidi = css
+            .get("unicode-bidi")
+            .and_then(|s| UnicodeBidi::parse(s))
+            .unwrap_or_default();
+
+        let text_decoration = TextDecoration::new(css, &context, color);
+        let text_shadow = css
+            .get("text-shadow")
+            .and_then(|value| Shadow::parse_list(value, &context, color))
+            .unwrap_or_default();
+        let text_stroke = TextStroke::new(css, &context, color);
 
         let text_align = css
             .get("text-align")
-            .or_else(|| all_selector.as_ref()?.get("text-align"))
             .map(|s| s.as_str())
-            .unwrap_or_else(|| "left")
+            .unwrap_or_else(|| "start")
             .into();
 
         Self {
             letter_spacing,
+            word_spacing,
+            white_space,
+            line_height,
             text_align,
             color,
             size,
-            family,
-            style,
             weight,
+            stretch,
+            variations,
+            description,
+            direction,
+            unicode_bidi,
+            faces,
+            text_decoration,
+            text_shadow,
+            text_stroke,
         }
     }
 }
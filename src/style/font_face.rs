@@ -0,0 +1,606 @@
+use super::font::{Font, FontWeight};
+use crate::parser::tokenizer::{self, Token};
+use crate::parser::{self, Declaration};
+use crate::RenderOptions;
+use std::collections::HashMap;
+
+// https://www.w3.org/TR/css-fonts-4/#font-face-rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    pub family: String,
+    // Tried in order, the first one that loads is used
+    pub sources: Vec<FontSource>,
+    // The range of weights the face is picked for
+    pub weight: [f64; 2],
+    pub style: pango::Style,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontSource {
+    // A font installed on the system, by its full name
+    Local(String),
+    // A TrueType, OpenType or WOFF2 file relative to `RenderOptions::base_dir`
+    Url(String),
+}
+
+// A family name is either a string or a sequence of identifiers, e.g. `"Inter"` or `Inter Display`
+pub(crate) fn family_name(value: &str) -> Option<String> {
+    if let Some(name) = parser::unquote(value) {
+        return Some(name);
+    }
+
+    let tokens = tokenizer::tokenize(value.trim());
+    let mut name = Vec::new();
+    for t in &tokens {
+        match &t.token {
+            Token::Ident(ident) => name.push(ident.as_str()),
+            Token::Whitespace => {}
+            _ => return None,
+        }
+    }
+
+    match name.is_empty() {
+        true => None,
+        false => Some(name.join(" ")),
+    }
+}
+
+fn source(value: &str) -> Option<FontSource> {
+    let tokens = tokenizer::tokenize(value.trim());
+    let tokens = tokens
+        .iter()
+        .map(|t| &t.token)
+        .filter(|t| **t != Token::Whitespace)
+        .collect::<Vec<_>>();
+
+    let (source, rest) = match tokens.as_slice() {
+        [Token::Url(url), rest @ ..] => (FontSource::Url(url.clone()), rest),
+        [Token::Function(function), Token::String(url), Token::CloseParen, rest @ ..]
+            if function.eq_ignore_ascii_case("url") =>
+        {
+            (FontSource::Url(url.clone()), rest)
+        }
+        [Token::Function(function), Token::String(name), Token::CloseParen]
+            if function.eq_ignore_ascii_case("local") =>
+        {
+            return Some(FontSource::Local(name.clone()))
+        }
+        _ => return None,
+    };
+
+    // Formats FreeType can't load are left out, and so is anything else after the url
+    match rest {
+        [] => Some(source),
+        [Token::Function(function), Token::String(format) | Token::Ident(format), Token::CloseParen]
+            if function.eq_ignore_ascii_case("format") =>
+        {
+            let supported = ["truetype", "opentype", "woff2", "collection"]
+                .iter()
+                .any(|supported| format.eq_ignore_ascii_case(supported));
+            supported.then_some(source)
+        }
+        _ => None,
+    }
+}
+
+// https://www.w3.org/TR/css-fonts-4/#font-weight-absolute-values
+pub(crate) fn weight(value: &str) -> Option<FontWeight> {
+    match value.to_ascii_lowercase().as_str() {
+        "normal" => FontWeight::new(400.),
+        "bold" => FontWeight::new(700.),
+        value => FontWeight::new(value.parse::<f64>().ok()?),
+    }
+}
+
+// One weight, or the range of weights a variable font covers
+fn weight_range(value: &str) -> Option<[f64; 2]> {
+    match parser::split_values(value).as_slice() {
+        [single] => weight(single).map(|weight| [weight.get(); 2]),
+        [min, max] => {
+            Some([weight(min)?.get(), weight(max)?.get()]).filter(|[min, max]| min <= max)
+        }
+        _ => None,
+    }
+}
+
+fn style(value: &str) -> Option<pango::Style> {
+    match parser::split_values(value)
+        .first()?
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "normal" => Some(pango::Style::Normal),
+        "italic" => Some(pango::Style::Italic),
+        // The angle of `oblique 10deg` is left to the font
+        "oblique" => Some(pango::Style::Oblique),
+        _ => None,
+    }
+}
+
+// Returns None for descriptors that are not supported, which are ignored
+pub(crate) fn validate_descriptor(name: &str, value: &str) -> Option<Result<(), &'static str>> {
+    let result = match name {
+        "font-family" => family_name(value)
+            .map(|_| ())
+            .ok_or("Expected a family name"),
+        "src" => {
+            let sources = parser::split_list(value);
+            let valid = sources.iter().all(|value| {
+                source(value).is_some()
+                    // Unsupported formats are skipped rather than invalid
+                    || value.to_ascii_lowercase().contains("format(")
+            });
+            valid
+                .then_some(())
+                .ok_or("Expected a list of `url()`s and `local()`s")
+        }
+        "font-weight" => weight_range(value)
+            .map(|_| ())
+            .ok_or("Expected one or two weights between 1 and 1000"),
+        "font-style" => style(value)
+            .map(|_| ())
+            .ok_or("Expected normal, italic or oblique"),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+impl FontFace {
+    // The descriptors have been validated, a face needs a family and at least one source
+    pub(crate) fn new(declarations: &[Declaration]) -> Option<Self> {
+        let get = |name: &str| {
+            declarations
+                .iter()
+                .rev()
+                .find(|declaration| declaration.name == name)
+                .map(|declaration| declaration.value.as_str())
+        };
+
+        let sources = parser::split_list(get("src")?)
+            .into_iter()
+            .filter_map(source)
+            .collect::<Vec<_>>();
+        if sources.is_empty() {
+            return None;
+        }
+
+        Some(FontFace {
+            family: family_name(get("font-family")?)?,
+            sources,
+            weight: get("font-weight")
+                .and_then(weight_range)
+                .unwrap_or([400.; 2]),
+            style: get("font-style")
+                .and_then(style)
+                .unwrap_or(pango::Style::Normal),
+        })
+    }
+}
+
+// https://www.w3.org/TR/css-fonts-4/#font-style-matching
+// Picks the face closest to the style and then to the weight asked for
+pub(crate) fn match_face<'a>(
+    faces: &[&'a FontFace],
+    style: pango::Style,
+    weight: f64,
+) -> Option<&'a FontFace> {
+    let style_order = match style {
+        pango::Style::Italic => [
+            pango::Style::Italic,
+            pango::Style::Oblique,
+            pango::Style::Normal,
+        ],
+        pango::Style::Oblique => [
+            pango::Style::Oblique,
+            pango::Style::Italic,
+            pango::Style::Normal,
+        ],
+        _ => [
+            pango::Style::Normal,
+            pango::Style::Oblique,
+            pango::Style::Italic,
+        ],
+    };
+    let style = style_order
+        .into_iter()
+        .find(|style| faces.iter().any(|face| face.style == *style))?;
+
+    // Lighter weights look for lighter faces first and heavier ones for heavier faces, with
+    // weights from 400 to 500 trying up to 500 before anything lighter
+    let distance = |&[min, max]: &[f64; 2]| match weight {
+        _ if min <= weight && weight <= max => (0, 0.),
+        _ if (400.0..=500.).contains(&weight) && min > weight && min <= 500. => (1, min - weight),
+        _ if weight <= 500. && max < weight => (2, weight - max),
+        _ if weight <= 500. => (3, min - weight),
+        _ if min > weight => (1, min - weight),
+        _ => (2, weight - max),
+    };
+
+    faces
+        .iter()
+        .filter(|face| face.style == style)
+        .min_by(|a, b| {
+            distance(&a.weight)
+                .partial_cmp(&distance(&b.weight))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .copied()
+}
+
+// Fonts loaded from files are handed to Pango through fontconfig, which its cairo font maps use
+// everywhere but Windows and macOS
+#[cfg(all(unix, not(target_vendor = "apple")))]
+mod fontconfig {
+    use super::FontFace;
+    use fontconfig_sys as fc;
+    use pango::glib::{self, prelude::*, translate::ToGlibPtr};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::ffi::CString;
+    use std::path::Path;
+    use std::ptr::NonNull;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Only ever handled through pointers
+    #[repr(C)]
+    struct PangoFcFontMap {
+        _private: [u8; 0],
+    }
+
+    // Not covered by the pango crates. The build script links pangoft2 and checks this against
+    // pango/pangofc-fontmap.h
+    extern "C" {
+        fn pango_fc_font_map_set_config(
+            fcfontmap: *mut PangoFcFontMap,
+            fcconfig: *mut fc::FcConfig,
+        );
+    }
+    const _: unsafe extern "C" fn(*mut PangoFcFontMap, *mut fc::FcConfig) =
+        pango_fc_font_map_set_config;
+
+    // A reference to a fontconfig config, released when dropped
+    struct Config(NonNull<fc::FcConfig>);
+
+    impl Config {
+        // The fonts the system is configured with
+        fn new() -> Option<Self> {
+            // SAFETY: returns a new reference, or null when the configuration fails to load
+            NonNull::new(unsafe { fc::FcInitLoadConfigAndFonts() }).map(Config)
+        }
+
+        // Adds a font file to the application fonts
+        fn add_file(&self, path: &Path) -> bool {
+            let Ok(file) = CString::new(path.as_os_str().as_encoded_bytes()) else {
+                return false;
+            };
+            // SAFETY: the config is alive and fontconfig copies the file name
+            unsafe { fc::FcConfigAppFontAddFile(self.0.as_ptr(), file.as_ptr() as *const _) != 0 }
+        }
+
+        // Adds the rules of a fontconfig configuration file, which fontconfig can only read from
+        // disk
+        fn add_rules(&self, rules: &str) -> bool {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let count = COUNT.fetch_add(1, Ordering::Relaxed);
+            let path =
+                std::env::temp_dir().join(format!("css-image-{}-{count}.conf", std::process::id()));
+            if std::fs::write(&path, rules).is_err() {
+                return false;
+            }
+
+            let added = CString::new(path.as_os_str().as_encoded_bytes()).is_ok_and(|file| {
+                // SAFETY: the config is alive and the file is read before this returns
+                unsafe {
+                    fc::FcConfigParseAndLoad(self.0.as_ptr(), file.as_ptr() as *const _, 1) != 0
+                }
+            });
+            let _ = std::fs::remove_file(&path);
+            added
+        }
+    }
+
+    impl Clone for Config {
+        fn clone(&self) -> Self {
+            // SAFETY: the config is alive, and the new reference is released on drop
+            unsafe { fc::FcConfigReference(self.0.as_ptr()) };
+            Config(self.0)
+        }
+    }
+
+    impl Drop for Config {
+        fn drop(&mut self) {
+            // SAFETY: releases this reference only, font maps and clones keep their own
+            unsafe { fc::FcConfigDestroy(self.0.as_ptr()) };
+        }
+    }
+
+    // A fontconfig font map, along with the config it finds fonts in
+    struct FontMap {
+        font_map: pango::FontMap,
+        _config: Config,
+    }
+
+    impl FontMap {
+        fn new(config: &Config) -> Option<Self> {
+            let font_map = pangocairo::FontMap::for_font_type(cairo::FontType::FontTypeFt)?;
+            // Only fontconfig font maps have a config to set
+            let fc_font_map = glib::Type::from_name("PangoFcFontMap")?;
+            if !font_map.type_().is_a(fc_font_map) {
+                return None;
+            }
+
+            let pointer: *mut pango::ffi::PangoFontMap = font_map.to_glib_none().0;
+            // SAFETY: the font map was just checked to be a PangoFcFontMap, and the config is
+            // kept alive with it
+            unsafe {
+                pango_fc_font_map_set_config(pointer as *mut PangoFcFontMap, config.0.as_ptr())
+            };
+            Some(FontMap {
+                font_map,
+                _config: config.clone(),
+            })
+        }
+    }
+
+    // The system fonts and the font files loaded so far, with a font map that can find all of
+    // them. Each thread has its own, so rendering in parallel never waits on the others
+    struct Fonts {
+        config: Config,
+        // Whether each file loaded, by the rule that registers it
+        loaded: HashMap<String, bool>,
+        // Only sees the fonts that were loaded when it was made
+        font_map: Option<FontMap>,
+    }
+
+    thread_local! {
+        static FONTS: RefCell<Option<Fonts>> = const { RefCell::new(None) };
+    }
+
+    fn with_fonts<T>(f: impl FnOnce(&mut Fonts) -> Option<T>) -> Option<T> {
+        FONTS.with_borrow_mut(|fonts| {
+            if fonts.is_none() {
+                *fonts = Some(Fonts {
+                    config: Config::new()?,
+                    loaded: HashMap::new(),
+                    font_map: None,
+                });
+            }
+            f(fonts.as_mut()?)
+        })
+    }
+
+    // https://www.w3.org/TR/css-fonts-4/#font-weight-absolute-values to fontconfig's weights, as
+    // fontconfig maps the OpenType weights in font files
+    fn fc_weight(weight: f64) -> f64 {
+        const WEIGHTS: [(f64, f64); 12] = [
+            (100., 0.),
+            (200., 40.),
+            (300., 50.),
+            (350., 55.),
+            (380., 75.),
+            (400., 80.),
+            (500., 100.),
+            (600., 180.),
+            (700., 200.),
+            (800., 205.),
+            (900., 210.),
+            (1000., 215.),
+        ];
+
+        let weight = weight.clamp(100., 1000.);
+        WEIGHTS
+            .windows(2)
+            .find(|pair| weight <= pair[1].0)
+            .map(|pair| {
+                let [(from, fc_from), (to, fc_to)] = [pair[0], pair[1]];
+                fc_from + (weight - from) / (to - from) * (fc_to - fc_from)
+            })
+            .unwrap_or(215.)
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    // A rule that gives the faces in the file the family, weights and style the face declares,
+    // whatever the file itself says
+    fn rule(path: &Path, face: &FontFace) -> Option<String> {
+        let weight = match face.weight.map(fc_weight) {
+            [min, max] if min == max => format!("<double>{min}</double>"),
+            [min, max] => format!("<range><double>{min}</double><double>{max}</double></range>"),
+        };
+        let slant = match face.style {
+            pango::Style::Italic => fc::constants::FC_SLANT_ITALIC,
+            pango::Style::Oblique => fc::constants::FC_SLANT_OBLIQUE,
+            _ => fc::constants::FC_SLANT_ROMAN,
+        };
+
+        Some(format!(
+            "<?xml version=\"1.0\"?>\n\
+             <!DOCTYPE fontconfig SYSTEM \"urn:fontconfig:fonts.dtd\">\n\
+             <fontconfig><match target=\"scan\">\
+             <test name=\"file\"><string>{}</string></test>\
+             <edit name=\"family\" mode=\"assign_replace\"><string>{}</string></edit>\
+             <edit name=\"weight\" mode=\"assign_replace\">{weight}</edit>\
+             <edit name=\"slant\" mode=\"assign_replace\"><int>{slant}</int></edit>\
+             </match></fontconfig>\n",
+            escape(path.to_str()?),
+            escape(&face.family),
+        ))
+    }
+
+    // Loads a font file for a face, registered under the face's family and descriptors so the
+    // face can be found by them in `with_font_map`
+    pub(super) fn load(path: &Path, face: &FontFace) -> Option<String> {
+        let rule = rule(path, face)?;
+        with_fonts(|fonts| {
+            if let Some(loaded) = fonts.loaded.get(&rule) {
+                return loaded.then(|| face.family.clone());
+            }
+
+            // Scan rules apply to the faces of files added after them
+            let loaded = fonts.config.add_rules(&rule) && fonts.config.add_file(path);
+            if loaded {
+                fonts.font_map = None;
+            }
+            fonts.loaded.insert(rule, loaded);
+            loaded.then(|| face.family.clone())
+        })
+    }
+
+    // Runs `f` with a font map that can find every font file loaded so far
+    pub(crate) fn with_font_map<T>(f: impl FnOnce(&pango::FontMap) -> T) -> Option<T> {
+        let font_map = with_fonts(|fonts| {
+            if fonts.font_map.is_none() {
+                fonts.font_map = Some(FontMap::new(&fonts.config)?);
+            }
+            fonts
+                .font_map
+                .as_ref()
+                .map(|font_map| font_map.font_map.clone())
+        })?;
+
+        Some(f(&font_map))
+    }
+}
+
+// Without fontconfig only `local()` sources can be used
+#[cfg(not(all(unix, not(target_vendor = "apple"))))]
+mod fontconfig {
+    use super::FontFace;
+    use std::path::Path;
+
+    pub(super) fn load(_: &Path, _: &FontFace) -> Option<String> {
+        None
+    }
+
+    pub(crate) fn with_font_map<T>(f: impl FnOnce(&pango::FontMap) -> T) -> Option<T> {
+        Some(f(&pangocairo::FontMap::default()))
+    }
+}
+
+pub(crate) use fontconfig::with_font_map;
+
+// Loads the face from the first of its sources that loads, returning the family name it can be
+// found under in `with_font_map`
+fn load(face: &FontFace, options: &RenderOptions) -> Option<String> {
+    face.sources.iter().find_map(|source| match source {
+        FontSource::Local(name) => Some(name.clone()),
+        FontSource::Url(url) => fontconfig::load(&options.file(url)?, face),
+    })
+}
+
+// The font's description, after loading the faces that match it best for the families that
+// `@font-face` rules declare. `local()` faces swap in the name of the system font, so this also
+// returns the family each face was declared as
+pub(crate) fn description(
+    font: &Font,
+    options: &RenderOptions,
+) -> (pango::FontDescription, HashMap<String, String>) {
+    let mut description = font.description.clone();
+    let style = description.style();
+    let weight = font.weight.get();
+
+    let mut declared = HashMap::new();
+    let families = description.family().unwrap_or_default();
+    let families = families
+        .split(',')
+        .map(|family| {
+            let faces = font
+                .faces
+                .iter()
+                .filter(|face| face.family.eq_ignore_ascii_case(family))
+                .collect::<Vec<_>>();
+            let face = match_face(&faces, style, weight);
+            match face.and_then(|face| load(face, options)) {
+                Some(loaded) => {
+                    declared.insert(loaded.clone(), family.to_string());
+                    loaded
+                }
+                None => family.to_string(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    description.set_family(&families.join(","));
+    (description, declared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(family_name("\"Inter\""), Some("Inter".into()));
+        assert_eq!(family_name("Inter  Display"), Some("Inter Display".into()));
+        assert_eq!(family_name("Inter, sans-serif"), None);
+
+        assert_eq!(
+            source("url(fonts/Inter.ttf) format(\"truetype\")"),
+            Some(FontSource::Url("fonts/Inter.ttf".into()))
+        );
+        assert_eq!(
+            source("local('Inter Bold')"),
+            Some(FontSource::Local("Inter Bold".into()))
+        );
+        assert_eq!(source("url(Inter.woff) format(woff)"), None);
+        assert_eq!(source("url(a.ttf) url(b.ttf)"), None);
+
+        assert_eq!(weight_range("bold"), Some([700.; 2]));
+        assert_eq!(weight_range("100 900"), Some([100., 900.]));
+        assert_eq!(weight_range("900 100"), None);
+        assert_eq!(weight_range("0"), None);
+        assert_eq!(style("oblique 10deg"), Some(pango::Style::Oblique));
+
+        assert!(
+            validate_descriptor("src", "url(a.woff) format(woff), url(a.ttf)")
+                .unwrap()
+                .is_ok()
+        );
+        assert!(validate_descriptor("src", "a.ttf").unwrap().is_err());
+        assert_eq!(validate_descriptor("font-display", "swap"), None);
+    }
+
+    #[test]
+    fn test_match_face() {
+        let face = |weight, style| FontFace {
+            family: "a".into(),
+            sources: Vec::new(),
+            weight,
+            style,
+        };
+        let faces = [
+            face([300.; 2], pango::Style::Normal),
+            face([600.; 2], pango::Style::Normal),
+            face([800.; 2], pango::Style::Normal),
+            face([400., 500.], pango::Style::Italic),
+        ];
+        let faces = faces.iter().collect::<Vec<_>>();
+        let matched = |style, weight| {
+            match_face(&faces, style, weight).map(|face| (face.weight[0], face.style))
+        };
+
+        assert_eq!(
+            matched(pango::Style::Normal, 400.),
+            Some((300., pango::Style::Normal))
+        );
+        assert_eq!(
+            matched(pango::Style::Normal, 700.),
+            Some((800., pango::Style::Normal))
+        );
+        assert_eq!(
+            matched(pango::Style::Normal, 200.),
+            Some((300., pango::Style::Normal))
+        );
+        assert_eq!(
+            matched(pango::Style::Oblique, 900.),
+            Some((400., pango::Style::Italic))
+        );
+        assert_eq!(match_face(&[], pango::Style::Normal, 400.), None);
+    }
+}
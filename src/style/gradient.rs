@@ -0,0 +1,716 @@
+use super::background::Position;
+use super::get_color;
+use super::length::{Length, LengthContext, LengthPercentage};
+use crate::parser::{
+    self,
+    tokenizer::{self, Token},
+};
+use cairo::{Context, Extend, Matrix, MeshCorner};
+use std::f64::consts::{PI, TAU};
+
+// Color stops within a gradient, resolved to fractions of the gradient line
+type Stops = Vec<(f64, [f64; 4])>;
+
+// https://www.w3.org/TR/css-images-4/#gradients
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<ColorStop>,
+    pub repeating: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GradientKind {
+    Linear(Direction),
+    Radial {
+        circle: bool,
+        size: RadialSize,
+        position: Position,
+    },
+    // The angle the gradient starts at, in radians clockwise from the top
+    Conic {
+        from: f64,
+        position: Position,
+    },
+}
+
+// Angles are in radians clockwise from the top. Corners are given by the signs of the direction
+// they are in, e.g. `to top right` is 1 and -1, as their angle depends on the box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Angle(f64),
+    Corner(f64, f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RadialSize {
+    ClosestSide,
+    FarthestSide,
+    ClosestCorner,
+    FarthestCorner,
+    Explicit(LengthPercentage, LengthPercentage),
+}
+
+// Positions of conic gradients are percentages of a full turn
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorStop {
+    Color([f64; 4], Option<LengthPercentage>),
+    Hint(LengthPercentage),
+}
+
+// Angles in radians, unitless zero is allowed like for lengths
+fn angle(value: &str) -> Option<f64> {
+    let tokens = tokenizer::tokenize(value.trim());
+    let angle = match &tokens.first()?.token {
+        _ if tokens.len() > 1 => return None,
+        Token::Number(value) if *value == 0. => 0.,
+        Token::Dimension(value, unit) => match unit.to_ascii_lowercase().as_str() {
+            "deg" => value.to_radians(),
+            "grad" => value * PI / 200.,
+            "rad" => *value,
+            "turn" => value * TAU,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(angle)
+}
+
+// `to` followed by one side, or two sides making up a corner
+fn direction(values: &[&str]) -> Option<Direction> {
+    let side = |value: &str| match value.to_ascii_lowercase().as_str() {
+        "top" => Some((0., -1.)),
+        "right" => Some((1., 0.)),
+        "bottom" => Some((0., 1.)),
+        "left" => Some((-1., 0.)),
+        _ => None,
+    };
+
+    match *values {
+        [value] => angle(value).map(Direction::Angle),
+        [to, value] if to.eq_ignore_ascii_case("to") => {
+            let (x, y): (f64, f64) = side(value)?;
+            Some(Direction::Angle(x.atan2(-y)))
+        }
+        [to, first, second] if to.eq_ignore_ascii_case("to") => {
+            match (side(first)?, side(second)?) {
+                ((x, 0.), (0., y)) | ((0., y), (x, 0.)) => Some(Direction::Corner(x, y)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// `circle` or `ellipse` and their size in either order, followed by `at` and a position
+fn radial(values: &[&str], context: &LengthContext) -> Option<GradientKind> {
+    let at = values.iter().position(|v| v.eq_ignore_ascii_case("at"));
+    let position = match at {
+        Some(at) => Position::parse(&values[at + 1..], context)?,
+        None => Position::center(),
+    };
+
+    let mut shape = None;
+    let mut size = None;
+    let mut lengths = Vec::new();
+    for (i, value) in values[..at.unwrap_or(values.len())].iter().enumerate() {
+        let keyword = match value.to_ascii_lowercase().as_str() {
+            "circle" | "ellipse" if shape.is_none() => {
+                shape = Some((i, value.eq_ignore_ascii_case("circle")));
+                continue;
+            }
+            "closest-side" => RadialSize::ClosestSide,
+            "farthest-side" => RadialSize::FarthestSide,
+            "closest-corner" => RadialSize::ClosestCorner,
+            "farthest-corner" => RadialSize::FarthestCorner,
+            _ => {
+                lengths.push((i, Length::parse(value).filter(|l| !l.is_negative())?));
+                continue;
+            }
+        };
+        if size.replace(keyword).is_some() {
+            return None;
+        }
+    }
+
+    // The shape can't come in between the radii
+    if let (Some((shape, _)), [(first, _), .., (last, _)]) = (shape, lengths.as_slice()) {
+        if (*first..*last).contains(&shape) {
+            return None;
+        }
+    }
+    let mut shape = shape.map(|(_, circle)| circle);
+    let lengths = lengths.into_iter().map(|(_, l)| l).collect::<Vec<_>>();
+
+    // A single length is the radius of a circle, two are the radii of an ellipse
+    let size = match (shape, lengths.len(), size) {
+        (_, 0, size) => size.unwrap_or(RadialSize::FarthestCorner),
+        (None | Some(true), 1, None) if !lengths[0].is_percent() => {
+            shape = Some(true);
+            let radius = LengthPercentage::new(lengths[0].clone(), context);
+            RadialSize::Explicit(radius.clone(), radius)
+        }
+        (None | Some(false), 2, None) => {
+            shape = Some(false);
+            let [x, y] = [0, 1].map(|i| LengthPercentage::new(lengths[i].clone(), context));
+            RadialSize::Explicit(x, y)
+        }
+        _ => return None,
+    };
+
+    Some(GradientKind::Radial {
+        circle: shape.unwrap_or(false),
+        size,
+        position,
+    })
+}
+
+// `from` and an angle, followed by `at` and a position
+fn conic(values: &[&str], context: &LengthContext) -> Option<GradientKind> {
+    let (from, values) = match values {
+        [from, value, rest @ ..] if from.eq_ignore_ascii_case("from") => (angle(value)?, rest),
+        values => (0., values),
+    };
+    let position = match values {
+        [] => Position::center(),
+        [at, position @ ..] if at.eq_ignore_ascii_case("at") => Position::parse(position, context)?,
+        _ => return None,
+    };
+
+    Some(GradientKind::Conic { from, position })
+}
+
+// Color stops with up to two positions each, with optional hints between them
+fn color_stops(
+    args: &[&str],
+    context: &LengthContext,
+    current_color: [f64; 4],
+    conic: bool,
+) -> Option<Vec<ColorStop>> {
+    let position = |value: &str| match conic {
+        true => match Length::parse(value) {
+            Some(Length::Percent(percent)) => Some(LengthPercentage::percent(percent)),
+            _ => angle(value).map(|angle| LengthPercentage::percent(angle / TAU * 100.)),
+        },
+        false => Length::parse(value).map(|length| LengthPercentage::new(length, context)),
+    };
+
+    let mut stops = Vec::new();
+    for arg in args {
+        match parser::split_values(arg).as_slice() {
+            [hint] if position(hint).is_some() => {
+                if !matches!(stops.last(), Some(ColorStop::Color(..))) {
+                    return None;
+                }
+                stops.push(ColorStop::Hint(position(hint)?));
+            }
+            [color, positions @ ..] if positions.len() <= 2 => {
+                let color = get_color(color, current_color)?;
+                if positions.is_empty() {
+                    stops.push(ColorStop::Color(color, None));
+                }
+                for value in positions {
+                    stops.push(ColorStop::Color(color, Some(position(value)?)));
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    let colors = stops
+        .iter()
+        .filter(|stop| matches!(stop, ColorStop::Color(..)))
+        .count();
+    match colors >= 2 && matches!(stops.last(), Some(ColorStop::Color(..))) {
+        true => Some(stops),
+        false => None,
+    }
+}
+
+// Interpolates between two colors with premultiplied alpha
+fn mix(from: [f64; 4], to: [f64; 4], amount: f64) -> [f64; 4] {
+    let alpha = from[3] + (to[3] - from[3]) * amount;
+    if alpha <= 0. {
+        return [0.; 4];
+    }
+    let channel = |i: usize| (from[i] * from[3] * (1. - amount) + to[i] * to[3] * amount) / alpha;
+    [channel(0), channel(1), channel(2), alpha]
+}
+
+// The color at a fraction of the gradient line, stops before the first and after the last one
+// take their color
+fn color_at(stops: &[(f64, [f64; 4])], offset: f64) -> [f64; 4] {
+    match stops.iter().position(|(position, _)| *position > offset) {
+        None => stops[stops.len() - 1].1,
+        Some(0) => stops[0].1,
+        Some(i) => {
+            let (start, from) = stops[i - 1];
+            let (end, to) = stops[i];
+            mix(from, to, (offset - start) / (end - start))
+        }
+    }
+}
+
+// Each of the radii of a radial gradient with its center at `x` and `y` inside of the box
+fn radial_size(
+    circle: bool,
+    size: &RadialSize,
+    (x, y): (f64, f64),
+    width: f64,
+    height: f64,
+) -> (f64, f64) {
+    let horizontal = [x.abs(), (width - x).abs()];
+    let vertical = [y.abs(), (height - y).abs()];
+    let closest = |[a, b]: [f64; 2]| a.min(b);
+    let farthest = |[a, b]: [f64; 2]| a.max(b);
+
+    match size {
+        RadialSize::ClosestSide | RadialSize::FarthestSide => {
+            let pick = match size {
+                RadialSize::ClosestSide => closest,
+                _ => farthest,
+            };
+            match circle {
+                true => {
+                    let radius = pick([pick(horizontal), pick(vertical)]);
+                    (radius, radius)
+                }
+                false => (pick(horizontal), pick(vertical)),
+            }
+        }
+        // Ellipses keep the aspect ratio they have when sized to the sides
+        RadialSize::ClosestCorner | RadialSize::FarthestCorner => {
+            let pick = match size {
+                RadialSize::ClosestCorner => closest,
+                _ => farthest,
+            };
+            let (x, y) = (pick(horizontal), pick(vertical));
+            match circle {
+                true => {
+                    let radius = x.hypot(y);
+                    (radius, radius)
+                }
+                false => (x * 2f64.sqrt(), y * 2f64.sqrt()),
+            }
+        }
+        RadialSize::Explicit(x, y) => (x.resolve(width), y.resolve(height)),
+    }
+}
+
+impl Gradient {
+    pub(crate) fn parse(
+        value: &str,
+        context: &LengthContext,
+        current_color: [f64; 4],
+    ) -> Option<Self> {
+        let (name, args) = value.trim().strip_suffix(')')?.split_once('(')?;
+        let name = name.to_ascii_lowercase();
+        let (repeating, name) = match name.strip_prefix("repeating-") {
+            Some(name) => (true, name),
+            None => (false, name.as_str()),
+        };
+
+        // The first argument configures the gradient, unless it's already a color stop
+        let mut args = parser::split_list(args);
+        let first = parser::split_values(args.first()?);
+        let prelude = match first.first() {
+            Some(value) if get_color(value, current_color).is_none() => {
+                args.remove(0);
+                first
+            }
+            _ => Vec::new(),
+        };
+
+        let kind = match (name, prelude.is_empty()) {
+            ("linear-gradient", true) => GradientKind::Linear(Direction::Angle(PI)),
+            ("linear-gradient", false) => GradientKind::Linear(direction(&prelude)?),
+            ("radial-gradient", _) => radial(&prelude, context)?,
+            ("conic-gradient", _) => conic(&prelude, context)?,
+            _ => return None,
+        };
+        let conic = matches!(kind, GradientKind::Conic { .. });
+
+        Some(Self {
+            stops: color_stops(&args, context, current_color, conic)?,
+            kind,
+            repeating,
+        })
+    }
+
+    // https://www.w3.org/TR/css-images-4/#color-stop-fixup
+    // Resolves the stops against the length of the gradient line, with hints turned into the
+    // stops approximating their curve
+    fn resolve_stops(&self, length: f64) -> Stops {
+        const HINT_STEPS: usize = 16;
+        let fraction = |position: &LengthPercentage| match length > 0. {
+            true => position.resolve(length) / length,
+            false => 0.,
+        };
+
+        let mut colors = Vec::new();
+        let mut hints = Vec::new();
+        for stop in &self.stops {
+            match stop {
+                ColorStop::Color(color, position) => {
+                    colors.push((position.as_ref().map(fraction), *color))
+                }
+                ColorStop::Hint(position) => hints.push((colors.len() - 1, fraction(position))),
+            }
+        }
+
+        // The first and last stops default to the ends of the line, and no stop can come before
+        // the one preceding it
+        let last = colors.len() - 1;
+        colors[0].0.get_or_insert(0.);
+        colors[last].0.get_or_insert(1.);
+        let mut max = f64::NEG_INFINITY;
+        for (position, _) in &mut colors {
+            if let Some(position) = position {
+                *position = position.max(max);
+                max = *position;
+            }
+        }
+
+        // Stops without a position are spread evenly between the ones around them
+        let mut positions = colors.iter().map(|(p, _)| *p).collect::<Vec<_>>();
+        let mut start = 0;
+        for i in 1..positions.len() {
+            if let Some(end) = positions[i] {
+                let from = positions[start].unwrap_or(end);
+                let count = (i - start) as f64;
+                for (j, position) in positions[start + 1..i].iter_mut().enumerate() {
+                    *position = Some(from + (end - from) * (j + 1) as f64 / count);
+                }
+                start = i;
+            }
+        }
+
+        let mut stops = Vec::new();
+        for (i, (position, (_, color))) in positions.iter().zip(&colors).enumerate() {
+            let position = position.unwrap_or_default();
+            stops.push((position, *color));
+
+            let Some(&(_, hint)) = hints.iter().find(|(index, _)| *index == i) else {
+                continue;
+            };
+            let (end, next) = (positions[i + 1].unwrap_or_default(), colors[i + 1].1);
+            if end <= position {
+                continue;
+            }
+            // The hint is where the colors are mixed half and half
+            let hint = ((hint - position) / (end - position)).clamp(0., 1.);
+            if hint <= 0. {
+                stops.push((position, next));
+            } else if hint >= 1. {
+                stops.push((end, *color));
+            } else {
+                let exponent = 0.5f64.ln() / hint.ln();
+                for step in 1..HINT_STEPS {
+                    let progress = step as f64 / HINT_STEPS as f64;
+                    stops.push((
+                        position + (end - position) * progress,
+                        mix(*color, next, progress.powf(exponent)),
+                    ));
+                }
+            }
+        }
+
+        stops
+    }
+
+    // Paints the gradient sized to the box at `x` and `y`, outside of which it keeps on going
+    pub(crate) fn draw(
+        &self,
+        context: &Context,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<(), cairo::Error> {
+        // Cairo only takes offsets between 0 and 1, so the line spans from the first to the last
+        // stop, or further to cover the whole box when not repeating
+        let span = |stops: &Stops| {
+            let (first, last) = (stops[0].0, stops[stops.len() - 1].0);
+            match self.repeating && last - first > f64::EPSILON {
+                true => (first, last),
+                false => (first.min(0.), last.max(1.)),
+            }
+        };
+        let add_stops = |gradient: &cairo::Gradient, stops: &Stops, (start, end): (f64, f64)| {
+            gradient.set_extend(match self.repeating {
+                true => Extend::Repeat,
+                false => Extend::Pad,
+            });
+            for (position, color) in stops {
+                let offset = (position - start) / (end - start);
+                gradient.add_color_stop_rgba(offset, color[0], color[1], color[2], color[3]);
+            }
+        };
+
+        match &self.kind {
+            GradientKind::Linear(direction) => {
+                // https://www.w3.org/TR/css-images-3/#linear-gradient-syntax
+                let angle = match *direction {
+                    Direction::Angle(angle) => angle,
+                    Direction::Corner(x, y) => (x * height).atan2(-y * width),
+                };
+                let (dx, dy) = (angle.sin(), -angle.cos());
+                let length = (width * dx).abs() + (height * dy).abs();
+                let stops = self.resolve_stops(length);
+                let (start, end) = span(&stops);
+
+                let (center_x, center_y) = (x + width / 2., y + height / 2.);
+                let point = |t: f64| {
+                    (
+                        center_x + dx * length * (t - 0.5),
+                        center_y + dy * length * (t - 0.5),
+                    )
+                };
+                let ((x0, y0), (x1, y1)) = (point(start), point(end));
+                let gradient = cairo::LinearGradient::new(x0, y0, x1, y1);
+                add_stops(&gradient, &stops, (start, end));
+                context.set_source(&gradient)?;
+            }
+            GradientKind::Radial {
+                circle,
+                size,
+                position,
+            } => {
+                let center = (position.x.resolve(width), position.y.resolve(height));
+                let (radius_x, radius_y) = radial_size(*circle, size, center, width, height);
+                // Degenerate ellipses are drawn as really thin ones
+                let (radius_x, radius_y) = (radius_x.max(1e-3), radius_y.max(1e-3));
+                let mut stops = self.resolve_stops(radius_x);
+
+                // Circles can't have a negative radius, repeating gradients are moved forward by
+                // whole repetitions until they don't start before the center
+                let (mut start, mut end) = span(&stops);
+                if start < 0. {
+                    let shift = match self.repeating {
+                        true => (-start / (end - start)).ceil() * (end - start),
+                        false => -start,
+                    };
+                    match self.repeating {
+                        true => stops
+                            .iter_mut()
+                            .for_each(|(position, _)| *position += shift),
+                        false => stops
+                            .iter_mut()
+                            .for_each(|(position, _)| *position = position.max(0.)),
+                    }
+                    (start, end) = match self.repeating {
+                        true => (start + shift, end + shift),
+                        false => (0., end),
+                    };
+                }
+
+                let gradient =
+                    cairo::RadialGradient::new(0., 0., start * radius_x, 0., 0., end * radius_x);
+                add_stops(&gradient, &stops, (start, end));
+                // Ellipses are circles squashed vertically
+                let scale = radius_x / radius_y;
+                gradient.set_matrix(Matrix::new(
+                    1.,
+                    0.,
+                    0.,
+                    scale,
+                    -(x + center.0),
+                    -(y + center.1) * scale,
+                ));
+                context.set_source(&gradient)?;
+            }
+            GradientKind::Conic { from, position } => {
+                let mesh = self.conic_mesh(
+                    *from,
+                    (
+                        x + position.x.resolve(width),
+                        y + position.y.resolve(height),
+                    ),
+                    (x, y, width, height),
+                );
+                context.set_source(&mesh)?;
+            }
+        }
+
+        context.paint()
+    }
+
+    // Cairo has no conic gradients, so they are made out of thin triangles around the center,
+    // each blending between the colors at its two edges
+    fn conic_mesh(
+        &self,
+        from: f64,
+        (center_x, center_y): (f64, f64),
+        (x, y, width, height): (f64, f64, f64, f64),
+    ) -> cairo::Mesh {
+        const STEPS: usize = 360;
+        let stops = self.resolve_stops(1.);
+        let (first, last) = (stops[0].0, stops[stops.len() - 1].0);
+        let period = last - first;
+        let repeating = self.repeating && period > 1. / STEPS as f64;
+        let color = |offset: f64| match repeating {
+            true => color_at(&stops, first + (offset - first).rem_euclid(period)),
+            false => color_at(&stops, offset),
+        };
+
+        // Edges go wherever a stop is, so hard stops stay sharp
+        let mut edges = (0..=STEPS)
+            .map(|i| i as f64 / STEPS as f64)
+            .collect::<Vec<_>>();
+        for &(position, _) in &stops {
+            let repetitions = match repeating {
+                true => ((first / period).floor() as i64..=((1. - first) / period).ceil() as i64)
+                    .map(|i| position + i as f64 * period)
+                    .collect(),
+                false => vec![position],
+            };
+            edges.extend(repetitions.into_iter().filter(|p| *p > 0. && *p < 1.));
+        }
+        edges.sort_by(f64::total_cmp);
+        edges.dedup();
+
+        // Far enough for the triangles' outer edges to stay clear of the box
+        let radius = [
+            (x, y),
+            (x + width, y),
+            (x, y + height),
+            (x + width, y + height),
+        ]
+        .iter()
+        .map(|(x, y)| (x - center_x).hypot(y - center_y))
+        .fold(1., f64::max)
+            * 2.;
+        let point = |offset: f64| {
+            let angle = from + offset * TAU;
+            (
+                center_x + angle.sin() * radius,
+                center_y - angle.cos() * radius,
+            )
+        };
+
+        let mesh = cairo::Mesh::new();
+        for edge in edges.windows(2) {
+            let (start, end) = (edge[0], edge[1]);
+            let epsilon = (end - start) * 1e-6;
+            let [from, to] = [color(start + epsilon), color(end - epsilon)];
+            let [(x0, y0), (x1, y1)] = [point(start), point(end)];
+
+            mesh.begin_patch();
+            mesh.move_to(center_x, center_y);
+            mesh.line_to(x0, y0);
+            mesh.line_to(x1, y1);
+            mesh.line_to(center_x, center_y);
+            let corners = [
+                (MeshCorner::MeshCorner0, from),
+                (MeshCorner::MeshCorner1, from),
+                (MeshCorner::MeshCorner2, to),
+                (MeshCorner::MeshCorner3, to),
+            ];
+            for (corner, color) in corners {
+                mesh.set_corner_color_rgba(corner, color[0], color[1], color[2], color[3]);
+            }
+            mesh.end_patch();
+        }
+
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::color::INITIAL_COLOR;
+
+    fn parse(value: &str) -> Option<Gradient> {
+        Gradient::parse(value, &LengthContext::default(), INITIAL_COLOR)
+    }
+
+    #[test]
+    fn test_parse() {
+        let gradient = parse("linear-gradient(to top right, red, 30%, blue 10px 20px)").unwrap();
+        assert_eq!(
+            gradient.kind,
+            GradientKind::Linear(Direction::Corner(1., -1.))
+        );
+        assert_eq!(gradient.stops.len(), 4);
+        assert!(!gradient.repeating);
+        assert_eq!(
+            parse("linear-gradient(to left, red, blue)").unwrap().kind,
+            GradientKind::Linear(Direction::Angle(-PI / 2.))
+        );
+        assert_eq!(
+            parse("linear-gradient(0.25turn, red, blue)").unwrap().kind,
+            GradientKind::Linear(Direction::Angle(PI / 2.))
+        );
+        assert!(
+            parse("repeating-linear-gradient(red, blue 10px)")
+                .unwrap()
+                .repeating
+        );
+
+        let gradient = parse("radial-gradient(circle 10px at top left, red, blue)").unwrap();
+        let GradientKind::Radial { circle, size, .. } = gradient.kind else {
+            panic!("Expected a radial gradient");
+        };
+        assert!(circle);
+        assert!(matches!(size, RadialSize::Explicit(..)));
+        assert!(parse("radial-gradient(closest-side ellipse, red, blue)").is_some());
+        assert!(parse("radial-gradient(10px 20%, red, blue)").is_some());
+        assert!(parse("conic-gradient(from 90deg at 25% 75%, red, blue 0.5turn)").is_some());
+
+        assert!(parse("linear-gradient(red)").is_none());
+        assert!(parse("linear-gradient(red, 50%)").is_none());
+        assert!(parse("linear-gradient(red, 10%, 20%, blue)").is_none());
+        assert!(parse("linear-gradient(to top bottom, red, blue)").is_none());
+        assert!(parse("linear-gradient(45px, red, blue)").is_none());
+        assert!(parse("radial-gradient(circle 10% , red, blue)").is_none());
+        assert!(parse("radial-gradient(ellipse 10px, red, blue)").is_none());
+        assert!(parse("conic-gradient(red, blue 10px)").is_none());
+        assert!(parse("foo-gradient(red, blue)").is_none());
+    }
+
+    #[test]
+    fn test_resolve_stops() {
+        let stops = |value| parse(value).unwrap().resolve_stops(100.);
+
+        assert_eq!(
+            stops("linear-gradient(red, lime, blue)"),
+            [
+                (0., [1., 0., 0., 1.]),
+                (0.5, [0., 1., 0., 1.]),
+                (1., [0., 0., 1., 1.])
+            ]
+        );
+        assert_eq!(
+            stops("linear-gradient(red 40%, lime 20px, blue 50px 60%)"),
+            [
+                (0.4, [1., 0., 0., 1.]),
+                (0.4, [0., 1., 0., 1.]),
+                (0.5, [0., 0., 1., 1.]),
+                (0.6, [0., 0., 1., 1.])
+            ]
+        );
+
+        let hinted = stops("linear-gradient(black, 25%, white)");
+        assert_eq!(hinted.len(), 17);
+        assert_eq!(color_at(&hinted, 0.25), [0.5, 0.5, 0.5, 1.]);
+        assert_eq!(color_at(&hinted, 0.), [0., 0., 0., 1.]);
+        assert_eq!(color_at(&hinted, 1.), [1., 1., 1., 1.]);
+    }
+
+    #[test]
+    fn test_radial_size() {
+        let size = |circle, size| radial_size(circle, &size, (20., 10.), 100., 50.);
+
+        assert_eq!(size(false, RadialSize::ClosestSide), (20., 10.));
+        assert_eq!(size(true, RadialSize::ClosestSide), (10., 10.));
+        assert_eq!(size(false, RadialSize::FarthestSide), (80., 40.));
+        assert_eq!(
+            size(true, RadialSize::FarthestCorner),
+            (80f64.hypot(40.), 80f64.hypot(40.))
+        );
+        let (x, y) = size(false, RadialSize::ClosestCorner);
+        assert!((x - 20. * 2f64.sqrt()).abs() < 1e-9 && (y - 10. * 2f64.sqrt()).abs() < 1e-9);
+    }
+}
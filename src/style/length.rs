@@ -0,0 +1,200 @@
+use super::calc::Calc;
+use crate::parser::tokenizer::{self, Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Length {
+    // Absolute units are converted to px when parsed
+    Px(f64),
+    Em(f64),
+    Rem(f64),
+    Ex(f64),
+    Ch(f64),
+    Vw(f64),
+    Vh(f64),
+    Percent(f64),
+    Calc(Box<Calc>),
+}
+
+// What relative lengths resolve against
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct LengthContext {
+    pub font_size: f64,
+    pub root_font_size: f64,
+    pub viewport: Option<(f64, f64)>,
+}
+
+// Units of the other kinds of dimensions values can have
+const OTHER_UNITS: [&str; 10] = [
+    "deg", "grad", "rad", "turn", "s", "ms", "dpi", "dpcm", "dppx", "x",
+];
+
+// A length whose percentages are only resolved once rendering, when the box they are relative
+// to is known
+#[derive(Debug, Clone, PartialEq)]
+pub struct LengthPercentage {
+    length: Length,
+    context: LengthContext,
+}
+
+impl Length {
+    pub fn parse(value: &str) -> Option<Self> {
+        let tokens = tokenizer::tokenize(value.trim());
+        match tokens.as_slice() {
+            [t] => Self::from_token(&t.token),
+            _ => Calc::parse(value).map(|calc| Length::Calc(Box::new(calc))),
+        }
+    }
+
+    pub(crate) fn from_token(token: &Token) -> Option<Self> {
+        let length = match token {
+            Token::Number(value) if *value == 0. => Length::Px(0.),
+            Token::Percentage(value) => Length::Percent(*value),
+            Token::Dimension(value, unit) => match unit.to_ascii_lowercase().as_str() {
+                "px" => Length::Px(*value),
+                "in" => Length::Px(value * 96.),
+                "cm" => Length::Px(value * 96. / 2.54),
+                "mm" => Length::Px(value * 96. / 25.4),
+                "q" => Length::Px(value * 96. / 101.6),
+                "pt" => Length::Px(value * 96. / 72.),
+                "pc" => Length::Px(value * 16.),
+                "em" => Length::Em(*value),
+                "rem" => Length::Rem(*value),
+                "ex" => Length::Ex(*value),
+                "ch" => Length::Ch(*value),
+                "vw" => Length::Vw(*value),
+                "vh" => Length::Vh(*value),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(length)
+    }
+
+    pub fn is_percent(&self) -> bool {
+        match self {
+            Length::Percent(_) => true,
+            Length::Calc(calc) => calc.contains_percent(),
+            _ => false,
+        }
+    }
+
+    // Math functions are clamped to the allowed range once resolved instead
+    pub fn is_negative(&self) -> bool {
+        match *self {
+            Length::Px(v)
+            | Length::Em(v)
+            | Length::Rem(v)
+            | Length::Ex(v)
+            | Length::Ch(v)
+            | Length::Vw(v)
+            | Length::Vh(v)
+            | Length::Percent(v) => v < 0.,
+            Length::Calc(_) => false,
+        }
+    }
+
+    // Resolves to px, returns None when there is nothing to resolve a percentage or viewport unit against
+    pub fn resolve(&self, context: &LengthContext, percent_basis: Option<f64>) -> Option<f64> {
+        let px = match *self {
+            Length::Px(v) => v,
+            Length::Em(v) => v * context.font_size,
+            Length::Rem(v) => v * context.root_font_size,
+            // https://www.w3.org/TR/css-values-4/#ex, 0.5em is the fallback used when the
+            // font metrics aren't known
+            Length::Ex(v) | Length::Ch(v) => v * context.font_size * 0.5,
+            Length::Vw(v) => v * context.viewport?.0 / 100.,
+            Length::Vh(v) => v * context.viewport?.1 / 100.,
+            Length::Percent(v) => v * percent_basis? / 100.,
+            Length::Calc(ref calc) => calc.resolve(context, percent_basis)?,
+        };
+
+        Some(px).filter(|px| px.is_finite())
+    }
+}
+
+// The first unit in a value that isn't a unit of any kind, e.g. `foo` in `10foo`
+pub(crate) fn unknown_unit(value: &str) -> Option<String> {
+    tokenizer::tokenize(value)
+        .into_iter()
+        .find_map(|t| match &t.token {
+            Token::Dimension(_, unit)
+                if Length::from_token(&t.token).is_none()
+                    && !OTHER_UNITS.iter().any(|u| unit.eq_ignore_ascii_case(u)) =>
+            {
+                Some(unit.clone())
+            }
+            _ => None,
+        })
+}
+
+impl LengthPercentage {
+    pub(crate) fn new(length: Length, context: &LengthContext) -> Self {
+        Self {
+            length,
+            context: *context,
+        }
+    }
+
+    pub(crate) fn percent(percent: f64) -> Self {
+        Self::new(Length::Percent(percent), &LengthContext::default())
+    }
+
+    // Lengths that can't be resolved, e.g. viewport units without a viewport, count as 0
+    pub fn resolve(&self, basis: f64) -> f64 {
+        self.length
+            .resolve(&self.context, Some(basis))
+            .unwrap_or(0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Length::parse("10px"), Some(Length::Px(10.)));
+        assert_eq!(Length::parse("1in"), Some(Length::Px(96.)));
+        assert_eq!(Length::parse("12pt"), Some(Length::Px(16.)));
+        assert_eq!(Length::parse("1PC"), Some(Length::Px(16.)));
+        assert_eq!(Length::parse("1.2em"), Some(Length::Em(1.2)));
+        assert_eq!(Length::parse("50%"), Some(Length::Percent(50.)));
+        assert_eq!(Length::parse("0"), Some(Length::Px(0.)));
+        assert_eq!(Length::parse("10"), None);
+        assert_eq!(Length::parse("10foo"), None);
+        assert_eq!(unknown_unit("10foo"), Some("foo".into()));
+        assert_eq!(unknown_unit("calc(1px + 2Qux) 3em"), Some("Qux".into()));
+        assert_eq!(unknown_unit("linear-gradient(45deg, red 1px, blue)"), None);
+        assert_eq!(Length::parse("10px 5px"), None);
+        assert!(matches!(
+            Length::parse("calc(1em + 2px)"),
+            Some(Length::Calc(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let context = LengthContext {
+            font_size: 20.,
+            root_font_size: 16.,
+            viewport: Some((800., 600.)),
+        };
+        let resolve = |value| Length::parse(value).unwrap().resolve(&context, Some(200.));
+
+        assert_eq!(resolve("2.54cm"), Some(96.));
+        assert_eq!(resolve("0.5em"), Some(10.));
+        assert_eq!(resolve("2rem"), Some(32.));
+        assert_eq!(resolve("1ex"), Some(10.));
+        assert_eq!(resolve("10vw"), Some(80.));
+        assert_eq!(resolve("10vh"), Some(60.));
+        assert_eq!(resolve("25%"), Some(50.));
+
+        let context = LengthContext {
+            viewport: None,
+            ..context
+        };
+        assert_eq!(Length::parse("10vw").unwrap().resolve(&context, None), None);
+        assert_eq!(Length::parse("10%").unwrap().resolve(&context, None), None);
+    }
+}
@@ -0,0 +1,383 @@
+use super::color::INITIAL_COLOR;
+use super::get_color;
+use super::length::{Length, LengthContext};
+use super::rounded_rectangle;
+use crate::parser;
+use cairo::{Context, ImageSurface};
+
+// https://www.w3.org/TR/css-backgrounds-3/#box-shadow
+// https://www.w3.org/TR/css-text-decor-3/#text-shadow-property, which has no spread or inset
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shadow {
+    pub x: f64,
+    pub y: f64,
+    pub blur: f64,
+    pub spread: f64,
+    pub color: [f64; 4],
+    pub inset: bool,
+}
+
+// Splits a single shadow into whether it's inset, its two to four lengths and its color, which
+// can come before or after the lengths but not in between them
+fn split(value: &str) -> Option<(bool, Vec<Length>, Option<&str>)> {
+    let mut inset = false;
+    let mut lengths = Vec::new();
+    let mut color = None;
+    let mut previous_length = false;
+
+    for value in parser::split_values(value) {
+        let is_length = match Length::parse(value) {
+            Some(length) if length.is_percent() => return None,
+            Some(_) if !lengths.is_empty() && !previous_length => return None,
+            Some(length) => {
+                lengths.push(length);
+                true
+            }
+            None if value.eq_ignore_ascii_case("inset") && !inset => {
+                inset = true;
+                false
+            }
+            None if color.replace(value).is_some() => return None,
+            None => false,
+        };
+        previous_length = is_length;
+    }
+
+    match (2..=4).contains(&lengths.len()) {
+        true => Some((inset, lengths, color)),
+        false => None,
+    }
+}
+
+fn valid(value: &str, text: bool) -> bool {
+    split(value).is_some_and(|(inset, lengths, color)| {
+        !(text && (inset || lengths.len() > 3))
+            && !lengths.get(2).is_some_and(Length::is_negative)
+            && color.is_none_or(|color| get_color(color, INITIAL_COLOR).is_some())
+    })
+}
+
+// Returns None for properties that are not shadow related
+pub(super) fn validate(property: &str, value: &str) -> Option<Result<(), &'static str>> {
+    let (text, message) = match property {
+        "box-shadow" => (false, "Expected `none` or a list of shadows"),
+        "text-shadow" => (
+            true,
+            "Expected `none` or a list of shadows without spread or inset",
+        ),
+        _ => return None,
+    };
+
+    let valid = value.trim().eq_ignore_ascii_case("none")
+        || parser::split_list(value)
+            .into_iter()
+            .all(|value| valid(value, text));
+    Some(valid.then_some(()).ok_or(message))
+}
+
+impl Shadow {
+    // Parses a comma separated list of shadows, the first one of which is drawn on top
+    pub(crate) fn parse_list(
+        value: &str,
+        context: &LengthContext,
+        current_color: [f64; 4],
+    ) -> Option<Vec<Self>> {
+        if value.trim().eq_ignore_ascii_case("none") {
+            return Some(Vec::new());
+        }
+
+        parser::split_list(value)
+            .into_iter()
+            .map(|value| {
+                let (inset, lengths, color) = split(value)?;
+                let mut lengths = lengths
+                    .iter()
+                    .map(|length| length.resolve(context, None))
+                    .collect::<Option<Vec<_>>>()?;
+                lengths.resize(4, 0.);
+
+                Some(Shadow {
+                    x: lengths[0],
+                    y: lengths[1],
+                    blur: lengths[2].max(0.),
+                    spread: lengths[3],
+                    color: match color {
+                        Some(color) => get_color(color, current_color)?,
+                        None => current_color,
+                    },
+                    inset,
+                })
+            })
+            .collect()
+    }
+
+    // How far the blur reaches past the shadow's edge, a Gaussian is negligible beyond three
+    // standard deviations and the standard deviation is half of the blur radius
+    fn blur_extent(&self) -> f64 {
+        (self.blur * 1.5).ceil()
+    }
+
+    // How far an outer shadow reaches past each side of the border box, clockwise from the top
+    pub fn extents(&self) -> [f64; 4] {
+        if self.inset {
+            return [0.; 4];
+        }
+
+        let reach = self.spread + self.blur_extent();
+        [
+            reach - self.y,
+            reach + self.x,
+            reach + self.y,
+            reach - self.x,
+        ]
+        .map(|e| e.max(0.))
+    }
+
+    // Outer shadows are drawn around the border box and inset ones inside of the padding box,
+    // neither of them is clipped to the box here
+    pub(crate) fn draw(
+        &self,
+        context: &Context,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        radii: [[f64; 2]; 4],
+    ) -> Result<(), cairo::Error> {
+        let spread = match self.inset {
+            true => -self.spread,
+            false => self.spread,
+        };
+        let shape = (
+            x + self.x - spread,
+            y + self.y - spread,
+            width + spread * 2.,
+            height + spread * 2.,
+            radii.map(|radius| radius.map(|r| spread_radius(r, spread))),
+        );
+
+        // The mask covers whatever the blur can reach, which for inset shadows is everything
+        // around the hole the shape leaves
+        let bounds = match self.inset {
+            true => (x, y, width, height),
+            false => (shape.0, shape.1, shape.2, shape.3),
+        };
+        self.draw_mask(context, bounds, |mask_context| {
+            if self.inset {
+                let (x1, y1, x2, y2) = mask_context.clip_extents()?;
+                mask_context.set_fill_rule(cairo::FillRule::EvenOdd);
+                mask_context.rectangle(x1, y1, x2 - x1, y2 - y1);
+            }
+            if shape.2 > 0. && shape.3 > 0. {
+                rounded_rectangle(mask_context, shape.0, shape.1, shape.2, shape.3, shape.4);
+            }
+            mask_context.fill()
+        })
+    }
+
+    // Text shadows are what `paint` draws within the bounds, offset and blurred
+    pub(crate) fn draw_text(
+        &self,
+        context: &Context,
+        bounds: (f64, f64, f64, f64),
+        paint: impl FnOnce(&Context) -> Result<(), cairo::Error>,
+    ) -> Result<(), cairo::Error> {
+        let bounds = (bounds.0 + self.x, bounds.1 + self.y, bounds.2, bounds.3);
+        self.draw_mask(context, bounds, |mask_context| {
+            mask_context.translate(self.x, self.y);
+            paint(mask_context)
+        })
+    }
+
+    // Fills the shadow's color through a mask of what `paint` draws, blurred past the bounds
+    fn draw_mask(
+        &self,
+        context: &Context,
+        bounds: (f64, f64, f64, f64),
+        paint: impl FnOnce(&Context) -> Result<(), cairo::Error>,
+    ) -> Result<(), cairo::Error> {
+        let extent = self.blur_extent();
+        let left = (bounds.0 - extent).floor();
+        let top = (bounds.1 - extent).floor();
+        let mask_width = ((bounds.0 + bounds.2 + extent).ceil() - left) as i32;
+        let mask_height = ((bounds.1 + bounds.3 + extent).ceil() - top) as i32;
+        if mask_width <= 0 || mask_height <= 0 {
+            return Ok(());
+        }
+
+        let mut mask = ImageSurface::create(cairo::Format::A8, mask_width, mask_height)?;
+        {
+            let mask_context = Context::new(&mask)?;
+            mask_context.translate(-left, -top);
+            paint(&mask_context)?;
+        }
+
+        if self.blur > 0. {
+            let stride = mask.stride() as usize;
+            // The context drawing the mask is gone, so nothing else holds on to it
+            let mut data = mask.data().map_err(|error| match error {
+                cairo::BorrowError::Cairo(error) => error,
+                cairo::BorrowError::NonExclusive => cairo::Error::SurfaceFinished,
+            })?;
+            blur(
+                &mut data,
+                mask_width as usize,
+                mask_height as usize,
+                stride,
+                self.blur / 2.,
+            );
+        }
+
+        context.set_source_rgba(self.color[0], self.color[1], self.color[2], self.color[3]);
+        context.mask_surface(&mask, left, top)
+    }
+}
+
+// https://www.w3.org/TR/css-backgrounds-3/#shadow-shape
+// Spreading grows the radii by the spread distance, except that small radii grow less so
+// sharp corners stay sharp
+fn spread_radius(radius: f64, spread: f64) -> f64 {
+    if radius <= 0. {
+        return 0.;
+    }
+    if spread <= 0. || radius >= spread {
+        return (radius + spread).max(0.);
+    }
+    radius + spread * (1. + (radius / spread - 1.).powi(3))
+}
+
+// Separable Gaussian blur of an A8 image
+fn blur(data: &mut [u8], width: usize, height: usize, stride: usize, sigma: f64) {
+    let radius = (sigma * 3.).ceil() as usize;
+    let kernel = (0..=radius * 2)
+        .map(|i| {
+            let distance = i as f64 - radius as f64;
+            (-distance * distance / (2. * sigma * sigma)).exp()
+        })
+        .collect::<Vec<_>>();
+    let total = kernel.iter().sum::<f64>();
+
+    // Pixels outside of the image are transparent
+    let convolve = |get: &dyn Fn(usize) -> f64, position: usize, length: usize| {
+        kernel
+            .iter()
+            .enumerate()
+            .filter_map(|(i, weight)| {
+                let i = (position + i).checked_sub(radius).filter(|&i| i < length)?;
+                Some(get(i) * weight)
+            })
+            .sum::<f64>()
+            / total
+    };
+
+    let mut horizontal = vec![0.; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[y * width + x] = convolve(&|i| f64::from(data[y * stride + i]), x, width);
+        }
+    }
+    for y in 0..height {
+        for x in 0..width {
+            let value = convolve(&|i| horizontal[i * width + x], y, height);
+            data[y * stride + x] = value.round().clamp(0., 255.) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let context = LengthContext {
+            font_size: 10.,
+            ..Default::default()
+        };
+        let parse = |value| Shadow::parse_list(value, &context, [1., 0., 0., 1.]);
+
+        assert_eq!(parse("none"), Some(Vec::new()));
+        assert_eq!(
+            parse("1px 2px"),
+            Some(vec![Shadow {
+                x: 1.,
+                y: 2.,
+                blur: 0.,
+                spread: 0.,
+                color: [1., 0., 0., 1.],
+                inset: false,
+            }])
+        );
+        assert_eq!(
+            parse("inset 0 1em 2px -1px blue, rgb(0 0 0 / 50%) 3px 4px 5px inset"),
+            Some(vec![
+                Shadow {
+                    x: 0.,
+                    y: 10.,
+                    blur: 2.,
+                    spread: -1.,
+                    color: [0., 0., 1., 1.],
+                    inset: true,
+                },
+                Shadow {
+                    x: 3.,
+                    y: 4.,
+                    blur: 5.,
+                    spread: 0.,
+                    color: [0., 0., 0., 0.5],
+                    inset: true,
+                },
+            ])
+        );
+
+        assert_eq!(parse("1px"), None);
+        assert_eq!(parse("1px 2px 3px 4px 5px"), None);
+        assert_eq!(parse("1px 2px red 3px"), None);
+        assert_eq!(parse("1px inset 2px"), None);
+        assert_eq!(parse("1px 2px red blue"), None);
+        assert_eq!(parse("1px 2px 10%"), None);
+        assert_eq!(parse("1px 2px, none"), None);
+        assert_eq!(parse("1px 2px,"), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(
+            validate("box-shadow", "0 0 4px red, inset 1px 1px"),
+            Some(Ok(()))
+        );
+        assert!(validate("box-shadow", "0 0 -4px red").unwrap().is_err());
+        assert!(validate("box-shadow", "0 0 4px foo").unwrap().is_err());
+        assert_eq!(
+            validate("text-shadow", "0 0 4px red, 1px 1px"),
+            Some(Ok(()))
+        );
+        assert!(validate("text-shadow", "0 0 4px 1px red").unwrap().is_err());
+        assert!(validate("text-shadow", "inset 0 0 red").unwrap().is_err());
+        assert_eq!(validate("border", "1px solid red"), None);
+    }
+
+    #[test]
+    fn test_extents() {
+        let shadow = Shadow {
+            x: 2.,
+            y: -1.,
+            blur: 4.,
+            spread: 1.,
+            color: INITIAL_COLOR,
+            inset: false,
+        };
+        assert_eq!(shadow.extents(), [8., 9., 6., 5.]);
+        assert_eq!(
+            Shadow {
+                inset: true,
+                ..shadow
+            }
+            .extents(),
+            [0.; 4]
+        );
+        assert_eq!(spread_radius(0., 5.), 0.);
+        assert_eq!(spread_radius(10., 5.), 15.);
+        assert_eq!(spread_radius(10., -5.), 5.);
+    }
+}
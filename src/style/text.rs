@@ -0,0 +1,504 @@
+use super::font::Font;
+use super::font_face;
+use super::length::{Length, LengthContext};
+use crate::RenderOptions;
+use cairo::Context;
+use pango::prelude::{FontExt, FontMapExt};
+
+// https://www.w3.org/TR/css-text-3/#white-space-property
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WhiteSpace {
+    #[default]
+    Normal,
+    Nowrap,
+    Pre,
+    PreWrap,
+    PreLine,
+}
+
+// https://www.w3.org/TR/css-inline-3/#line-height-property
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineHeight {
+    // The font's own line spacing
+    #[default]
+    Normal,
+    // A multiple of the font size
+    Number(f64),
+    Px(f64),
+}
+
+// https://www.w3.org/TR/css-writing-modes-4/#direction
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextDirection {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+// https://www.w3.org/TR/css-writing-modes-4/#unicode-bidi
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UnicodeBidi {
+    #[default]
+    Normal,
+    Embed,
+    Isolate,
+    BidiOverride,
+    IsolateOverride,
+    Plaintext,
+}
+
+// A line box of the laid out content. `left` is how far the glyphs start from where the line is
+// drawn, and `width` leaves out the spaces hanging at the end of the line
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub text: String,
+    pub line: pango::LayoutLine,
+    pub left: f64,
+    pub width: f64,
+    // How far down from the top of the content the baseline is
+    pub baseline: f64,
+    // The direction of the line's paragraph, which `start` and `end` alignment follow
+    pub rtl: bool,
+    // The x, y, width and height the glyphs cover, from where the line is drawn
+    pub ink: (f64, f64, f64, f64),
+}
+
+// Where the font puts its decoration lines, as the distance from the baseline down to the top of
+// each line, and how thick it makes them
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DecorationMetrics {
+    pub ascent: f64,
+    pub underline: f64,
+    pub underline_thickness: f64,
+    pub line_through: f64,
+    pub line_through_thickness: f64,
+}
+
+// The content broken into shaped lines
+#[derive(Debug, Clone, Default)]
+pub struct TextLayout {
+    pub lines: Vec<Line>,
+    pub height: f64,
+    pub decoration: DecorationMetrics,
+    // The families the glyphs were found in, in order of first use
+    pub families: Vec<String>,
+}
+
+impl WhiteSpace {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let white_space = match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => WhiteSpace::Normal,
+            "nowrap" => WhiteSpace::Nowrap,
+            "pre" => WhiteSpace::Pre,
+            "pre-wrap" => WhiteSpace::PreWrap,
+            "pre-line" => WhiteSpace::PreLine,
+            _ => return None,
+        };
+        Some(white_space)
+    }
+
+    fn collapses_spaces(self) -> bool {
+        matches!(
+            self,
+            WhiteSpace::Normal | WhiteSpace::Nowrap | WhiteSpace::PreLine
+        )
+    }
+
+    fn preserves_newlines(self) -> bool {
+        !matches!(self, WhiteSpace::Normal | WhiteSpace::Nowrap)
+    }
+
+    fn wraps(self) -> bool {
+        !matches!(self, WhiteSpace::Nowrap | WhiteSpace::Pre)
+    }
+}
+
+impl TextDirection {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ltr" => Some(TextDirection::Ltr),
+            "rtl" => Some(TextDirection::Rtl),
+            _ => None,
+        }
+    }
+}
+
+impl UnicodeBidi {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let unicode_bidi = match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => UnicodeBidi::Normal,
+            "embed" => UnicodeBidi::Embed,
+            "isolate" => UnicodeBidi::Isolate,
+            "bidi-override" => UnicodeBidi::BidiOverride,
+            "isolate-override" => UnicodeBidi::IsolateOverride,
+            "plaintext" => UnicodeBidi::Plaintext,
+            _ => return None,
+        };
+        Some(unicode_bidi)
+    }
+}
+
+impl LineHeight {
+    // Percentages and lengths are resolved against the element's own font size
+    pub(crate) fn parse(value: &str, context: &LengthContext) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("normal") {
+            return Some(LineHeight::Normal);
+        }
+        if let Ok(number) = value.parse::<f64>() {
+            return (number >= 0.).then_some(LineHeight::Number(number));
+        }
+
+        let length = Length::parse(value).filter(|length| !length.is_negative())?;
+        length
+            .resolve(context, Some(context.font_size))
+            .map(LineHeight::Px)
+    }
+
+    pub fn resolve(&self, font_size: f64, normal: f64) -> f64 {
+        match self {
+            LineHeight::Normal => normal,
+            LineHeight::Number(number) => number * font_size,
+            LineHeight::Px(px) => *px,
+        }
+    }
+}
+
+// Returns None for properties that are not text related
+pub(super) fn validate(property: &str, value: &str) -> Option<Result<(), &'static str>> {
+    let result = match property {
+        "white-space" => WhiteSpace::parse(value)
+            .map(|_| ())
+            .ok_or("Expected `normal`, `nowrap`, `pre`, `pre-wrap` or `pre-line`"),
+        "line-height" => LineHeight::parse(value, &LengthContext::default())
+            .map(|_| ())
+            .ok_or("Expected `normal`, a number or a non-negative length"),
+        "direction" => TextDirection::parse(value)
+            .map(|_| ())
+            .ok_or("Expected `ltr` or `rtl`"),
+        "unicode-bidi" => UnicodeBidi::parse(value).map(|_| ()).ok_or(
+            "Expected `normal`, `embed`, `isolate`, `bidi-override`, `isolate-override` or `plaintext`",
+        ),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+// https://www.w3.org/TR/css-text-3/#white-space-processing
+// Splits the content into paragraphs at preserved newlines, collapsing spaces and expanding tabs
+// as `white-space` asks
+fn paragraphs(text: &str, white_space: WhiteSpace) -> Vec<String> {
+    let text = match white_space.preserves_newlines() {
+        true => text.replace("\r\n", "\n"),
+        false => text.replace(['\r', '\n'], " "),
+    };
+
+    text.split('\n')
+        .map(|paragraph| match white_space.collapses_spaces() {
+            true => paragraph.split_whitespace().collect::<Vec<_>>().join(" "),
+            // Tabs stop every eight spaces
+            false => {
+                let mut expanded = String::new();
+                for c in paragraph.chars() {
+                    match c {
+                        '\t' => {
+                            let column = expanded.chars().count();
+                            expanded.extend(std::iter::repeat_n(' ', 8 - column % 8));
+                        }
+                        c => expanded.push(c),
+                    }
+                }
+                expanded
+            }
+        })
+        .collect()
+}
+
+// https://www.w3.org/TR/css-text-3/#line-breaking
+// Shapes the content with Pango, which takes care of bidi reordering and of falling back to other
+// fonts for glyphs the font doesn't have. Lines break at spaces so they fit in `max_width` where
+// `white-space` allows it, words longer than a line overflow it
+pub(crate) fn layout(
+    context: &Context,
+    text: &str,
+    font: &Font,
+    max_width: Option<f64>,
+    options: &RenderOptions,
+) -> TextLayout {
+    // Fonts loaded for `@font-face` rules are only known to font maps of their own
+    if !font.faces.is_empty() {
+        let (description, declared) = font_face::description(font, options);
+        let layout = font_face::with_font_map(|font_map| {
+            let pango_context = font_map.create_context();
+            pangocairo::functions::update_context(context, &pango_context);
+            let layout = pango::Layout::new(&pango_context);
+            layout.set_font_description(Some(&description));
+            shape(layout, text, font, max_width)
+        });
+        if let Some(mut layout) = layout {
+            // `local()` faces go by the names of system fonts, report the families they were
+            // declared as
+            for family in &mut layout.families {
+                if let Some(declared) = declared.get(family) {
+                    family.clone_from(declared);
+                }
+            }
+            return layout;
+        }
+    }
+
+    let layout = pangocairo::functions::create_layout(context);
+    layout.set_font_description(Some(&font.description));
+    shape(layout, text, font, max_width)
+}
+
+fn shape(layout: pango::Layout, text: &str, font: &Font, max_width: Option<f64>) -> TextLayout {
+    // Every element is a root, so embedding and isolating its content changes nothing
+    if font.unicode_bidi == UnicodeBidi::Plaintext {
+        layout.set_auto_dir(true);
+    } else {
+        layout.set_auto_dir(false);
+        layout.context().set_base_dir(match font.direction {
+            TextDirection::Ltr => pango::Direction::Ltr,
+            TextDirection::Rtl => pango::Direction::Rtl,
+        });
+        layout.context_changed();
+    }
+
+    // Overrides end with each paragraph, so they are repeated for every one of them
+    let (start, end) = match (font.unicode_bidi, font.direction) {
+        (UnicodeBidi::BidiOverride | UnicodeBidi::IsolateOverride, TextDirection::Ltr) => {
+            ("\u{202D}", "\u{202C}")
+        }
+        (UnicodeBidi::BidiOverride | UnicodeBidi::IsolateOverride, TextDirection::Rtl) => {
+            ("\u{202E}", "\u{202C}")
+        }
+        _ => ("", ""),
+    };
+    let text = paragraphs(text, font.white_space)
+        .iter()
+        .map(|paragraph| format!("{start}{paragraph}{end}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    layout.set_text(&text);
+
+    // https://www.w3.org/TR/css-text-3/#spacing
+    // Word spacing is extra letter spacing on the spaces between words
+    let scale = f64::from(pango::SCALE);
+    let attributes = pango::AttrList::new();
+    if font.letter_spacing != 0. {
+        attributes.insert(pango::AttrInt::new_letter_spacing(
+            (font.letter_spacing * scale) as i32,
+        ));
+    }
+    if font.word_spacing != 0. {
+        for (i, c) in text
+            .char_indices()
+            .filter(|(_, c)| matches!(c, ' ' | '\u{A0}'))
+        {
+            let mut attribute = pango::AttrInt::new_letter_spacing(
+                ((font.letter_spacing + font.word_spacing) * scale) as i32,
+            );
+            attribute.set_start_index(i as u32);
+            attribute.set_end_index((i + c.len_utf8()) as u32);
+            attributes.insert(attribute);
+        }
+    }
+    layout.set_attributes(Some(&attributes));
+
+    if let Some(max_width) = max_width.filter(|_| font.white_space.wraps()) {
+        layout.set_width((max_width * f64::from(pango::SCALE)) as i32);
+        layout.set_wrap(pango::WrapMode::Word);
+    }
+
+    let mut height = 0.;
+    let lines = layout
+        .lines_readonly()
+        .into_iter()
+        .map(|line| {
+            let start = line.start_index() as usize;
+            let text = text[start..start + line.length() as usize].to_string();
+
+            // Half of the leading goes above the glyphs and half below
+            let (ink, logical) = line.extents();
+            let ascent = -f64::from(logical.y()) / scale;
+            let content_height = f64::from(logical.height()) / scale;
+            let line_height = font.line_height.resolve(font.size, content_height);
+            let baseline = height + (line_height - content_height) / 2. + ascent;
+            height += line_height;
+
+            // Spaces at the end of a line hang past it, on whichever side bidi reordering puts them
+            let (mut left, mut right) = (logical.x(), logical.x() + logical.width());
+            let trimmed = text.trim_end_matches([' ', '\u{202C}']).len();
+            if let Some((last, _)) = text[trimmed..].char_indices().last() {
+                let a = line.index_to_x((start + trimmed) as i32, false);
+                let b = line.index_to_x((start + trimmed + last) as i32, true);
+                if a.min(b) <= left {
+                    left = a.max(b);
+                } else {
+                    right = a.min(b);
+                }
+            }
+
+            Line {
+                text,
+                rtl: line.resolved_direction() == pango::Direction::Rtl,
+                line,
+                left: f64::from(left) / scale,
+                width: f64::from((right - left).max(0)) / scale,
+                baseline,
+                ink: (
+                    f64::from(ink.x()) / scale,
+                    f64::from(ink.y()) / scale,
+                    f64::from(ink.width()) / scale,
+                    f64::from(ink.height()) / scale,
+                ),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Fontconfig falls back family by family and then to any font with the glyphs. Runs have no
+    // font when there are no fonts at all
+    let description = layout.font_description().unwrap_or_default();
+    let runs = match layout.context().load_font(&description) {
+        Some(_) => lines.iter().flat_map(|line| line.line.runs()).collect(),
+        None => Vec::new(),
+    };
+    let mut families = Vec::new();
+    for run in runs {
+        let family = run.item().analysis().font().describe().family();
+        if let Some(family) = family.filter(|family| !families.contains(&family.to_string())) {
+            families.push(family.to_string());
+        }
+    }
+
+    let metrics = layout.context().metrics(Some(&description), None);
+    let decoration = DecorationMetrics {
+        ascent: f64::from(metrics.ascent()) / scale,
+        underline: -f64::from(metrics.underline_position()) / scale,
+        underline_thickness: f64::from(metrics.underline_thickness()) / scale,
+        line_through: -f64::from(metrics.strikethrough_position()) / scale,
+        line_through_thickness: f64::from(metrics.strikethrough_thickness()) / scale,
+    };
+
+    TextLayout {
+        lines,
+        height,
+        decoration,
+        families,
+    }
+}
+
+impl TextLayout {
+    pub fn width(&self) -> f64 {
+        self.lines.iter().map(|line| line.width).fold(0., f64::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(WhiteSpace::parse("pre-wrap"), Some(WhiteSpace::PreWrap));
+        assert_eq!(WhiteSpace::parse("wrap"), None);
+
+        let context = LengthContext {
+            font_size: 10.,
+            ..Default::default()
+        };
+        let parse = |value| LineHeight::parse(value, &context);
+        assert_eq!(parse("normal"), Some(LineHeight::Normal));
+        assert_eq!(parse("1.5"), Some(LineHeight::Number(1.5)));
+        assert_eq!(parse("150%"), Some(LineHeight::Px(15.)));
+        assert_eq!(parse("2em"), Some(LineHeight::Px(20.)));
+        assert_eq!(parse("-1"), None);
+        assert_eq!(parse("-1px"), None);
+        assert_eq!(parse("foo"), None);
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let text = " a \t b\n\n c ";
+        assert_eq!(paragraphs(text, WhiteSpace::Normal), ["a b c"]);
+        assert_eq!(paragraphs(text, WhiteSpace::PreLine), ["a b", "", "c"]);
+        assert_eq!(paragraphs(text, WhiteSpace::Pre), [" a       b", "", " c "]);
+    }
+
+    #[test]
+    fn test_layout() {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 0, 0).unwrap();
+        let context = Context::new(&surface).unwrap();
+        let font = |white_space| {
+            let css = [
+                ("font-size", "10px"),
+                ("line-height", "2"),
+                ("white-space", white_space),
+            ]
+            .into_iter()
+            .map(|(property, value)| (property.into(), value.to_string()))
+            .collect();
+            Font::new(&css, &Default::default(), &[])
+        };
+        let options = RenderOptions::default();
+        let word = layout(&context, "aaa", &font("normal"), None, &options).width();
+        let lines = |text, white_space, max_width| {
+            layout(&context, text, &font(white_space), max_width, &options)
+                .lines
+                .into_iter()
+                .map(|line| line.text)
+                .collect::<Vec<_>>()
+        };
+
+        let text = "aaa aaa  aaa\naaa";
+        let max_width = Some(word * 2.5);
+        assert_eq!(lines(text, "normal", max_width), ["aaa aaa ", "aaa aaa"]);
+        assert_eq!(lines(text, "nowrap", max_width), ["aaa aaa aaa aaa"]);
+        assert_eq!(lines(text, "pre", max_width), ["aaa aaa  aaa", "aaa"]);
+        assert_eq!(
+            lines(text, "pre-wrap", max_width),
+            ["aaa aaa  ", "aaa", "aaa"]
+        );
+        assert_eq!(
+            lines(text, "pre-line", Some(word)),
+            ["aaa ", "aaa ", "aaa", "aaa"]
+        );
+
+        let layout = layout(&context, "a\nb ", &font("pre"), None, &options);
+        assert_eq!(layout.height, 40.);
+        assert_eq!(layout.lines[1].baseline - layout.lines[0].baseline, 20.);
+        assert_eq!(layout.width(), word / 3.);
+        assert!(!layout.lines[0].rtl);
+    }
+
+    #[test]
+    fn test_spacing() {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 0, 0).unwrap();
+        let context = Context::new(&surface).unwrap();
+        let width = |letter_spacing: &str, word_spacing: &str| {
+            let css = [
+                ("letter-spacing", letter_spacing),
+                ("word-spacing", word_spacing),
+            ]
+            .into_iter()
+            .map(|(property, value)| (property.into(), value.to_string()))
+            .collect();
+            layout(
+                &context,
+                "aa aa",
+                &Font::new(&css, &Default::default(), &[]),
+                None,
+                &Default::default(),
+            )
+            .width()
+        };
+
+        let normal = width("normal", "normal");
+        // Letter spacing goes between the five letters, not around them
+        assert_eq!(width("2px", "normal"), normal + 8.);
+        assert_eq!(width("normal", "10px"), normal + 10.);
+        assert_eq!(width("1px", "0.5em"), normal + 4. + 6.);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::parser::tokenizer::{self, Token};
+use std::collections::{HashMap, HashSet};
+
+struct Resolver<'a> {
+    custom: HashMap<&'a str, &'a str>,
+    resolved: HashMap<String, Option<String>>,
+    stack: Vec<String>,
+    cyclic: HashSet<String>,
+}
+
+impl Resolver<'_> {
+    fn resolve(&mut self, name: &str) -> Option<String> {
+        if let Some(value) = self.resolved.get(name) {
+            return value.clone();
+        }
+        // Every variable taking part in a cycle is invalid
+        if let Some(start) = self.stack.iter().position(|n| n == name) {
+            self.cyclic.extend(self.stack[start..].iter().cloned());
+            return None;
+        }
+
+        let value = *self.custom.get(name)?;
+        self.stack.push(name.to_string());
+        let resolved = substitute_value(value, &mut |name| self.resolve(name));
+        self.stack.pop();
+
+        let resolved = resolved.filter(|_| !self.cyclic.contains(name));
+        self.resolved.insert(name.to_string(), resolved.clone());
+        resolved
+    }
+}
+
+// https://www.w3.org/TR/css-variables-1/#substitute-a-var
+// Properties that reference an undefined variable without a fallback, or a
+// variable caught in a cycle, are invalid at computed-value time and dropped
+pub(crate) fn substitute(
+    properties: &mut HashMap<Box<str>, String>,
+    overrides: &HashMap<String, String>,
+) {
+    for (name, value) in overrides {
+        properties.insert(name.as_str().into(), value.clone());
+    }
+
+    let custom = properties
+        .iter()
+        .filter(|(name, _)| name.starts_with("--"))
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect::<Vec<_>>();
+    let mut resolver = Resolver {
+        custom: custom
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect(),
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+        cyclic: HashSet::new(),
+    };
+
+    properties.retain(|name, value| {
+        let resolved = match name.starts_with("--") {
+            true => resolver.resolve(name),
+            false => substitute_value(value, &mut |name| resolver.resolve(name)),
+        };
+        match resolved {
+            Some(resolved) => {
+                *value = resolved;
+                true
+            }
+            None => false,
+        }
+    });
+}
+
+fn substitute_value(value: &str, lookup: &mut dyn FnMut(&str) -> Option<String>) -> Option<String> {
+    if !value.contains("var(") {
+        return Some(value.to_string());
+    }
+
+    let tokens = tokenizer::tokenize(value);
+    let mut result = String::new();
+    let mut last = 0;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if !matches!(&tokens[i].token, Token::Function(f) if f.eq_ignore_ascii_case("var")) {
+            i += 1;
+            continue;
+        }
+
+        let mut depth = 0;
+        let mut close = None;
+        let mut comma = None;
+        for (j, t) in tokens.iter().enumerate().skip(i + 1) {
+            match t.token {
+                Token::Function(_) | Token::OpenParen => depth += 1,
+                Token::CloseParen if depth == 0 => {
+                    close = Some(j);
+                    break;
+                }
+                Token::CloseParen => depth -= 1,
+                Token::Comma if depth == 0 && comma.is_none() => comma = Some(j),
+                _ => {}
+            }
+        }
+
+        let name = tokens[i + 1..comma.or(close).unwrap_or(tokens.len())]
+            .iter()
+            .filter(|t| t.token != Token::Whitespace)
+            .map(|t| &t.token)
+            .collect::<Vec<_>>();
+        let name = match name.as_slice() {
+            [Token::Ident(name)] if name.starts_with("--") => name,
+            _ => return None,
+        };
+
+        let end = close.map_or(value.len(), |close| tokens[close].span.start);
+        let replacement = match lookup(name) {
+            Some(replacement) => replacement,
+            None => {
+                let comma = comma?;
+                substitute_value(value[tokens[comma].span.end..end].trim(), lookup)?
+            }
+        };
+
+        result.push_str(&value[last..tokens[i].span.start]);
+        result.push_str(&replacement);
+        last = close.map_or(value.len(), |close| tokens[close].span.end);
+        i = close.map_or(tokens.len(), |close| close + 1);
+    }
+
+    result.push_str(&value[last..]);
+    Some(result.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(properties: &[(&str, &str)]) -> HashMap<Box<str>, String> {
+        properties
+            .iter()
+            .map(|(name, value)| ((*name).into(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_substitute() {
+        let mut css = properties(&[
+            ("--accent", "#ff0000"),
+            ("--border", "1px solid var(--accent)"),
+            ("color", "var(--accent)"),
+            ("border", "var(--border)"),
+            ("background-color", "var(--missing, var(--other, blue))"),
+            ("width", "var(--missing)"),
+            ("height", "calc(var(--size, 10px) * 2)"),
+        ]);
+        substitute(&mut css, &HashMap::new());
+
+        assert_eq!(css["color"], "#ff0000");
+        assert_eq!(css["border"], "1px solid #ff0000");
+        assert_eq!(css["background-color"], "blue");
+        assert!(!css.contains_key("width"));
+        assert_eq!(css["height"], "calc(10px * 2)");
+    }
+
+    #[test]
+    fn test_cycles() {
+        let mut css = properties(&[
+            ("--a", "var(--b)"),
+            ("--b", "var(--a)"),
+            ("--c", "var(--c, red)"),
+            ("--d", "green"),
+            ("--e", "var(--f, red)"),
+            ("--f", "var(--e, red)"),
+            ("color", "var(--a, var(--d))"),
+            ("background-color", "var(--c, blue)"),
+        ]);
+        substitute(&mut css, &HashMap::new());
+
+        assert!(!css.contains_key("--a"));
+        assert!(!css.contains_key("--b"));
+        assert!(!css.contains_key("--c"));
+        assert!(!css.contains_key("--e"));
+        assert!(!css.contains_key("--f"));
+        assert_eq!(css["color"], "green");
+        assert_eq!(css["background-color"], "blue");
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut css = properties(&[("--accent", "red"), ("color", "var(--accent)")]);
+        let overrides = [("--accent".to_string(), "white".to_string())].into();
+        substitute(&mut css, &overrides);
+        assert_eq!(css["color"], "white");
+    }
+}
@@ -1,6 +1,25 @@
 #[cfg(test)]
 mod tests {
-    use css_image::{parse, render};
+    use css_image::{
+        diagnostics::Severity, parse, parse_with_diagnostics, render, render_element, CssError,
+        Element, RenderOptions, Rendered, Resolver, Stylesheet,
+    };
+
+    fn png_size(png: &[u8]) -> (i32, i32) {
+        let surface = cairo::ImageSurface::create_from_png(&mut &png[..]).unwrap();
+        (surface.width(), surface.height())
+    }
+
+    // Returns the premultiplied ARGB of a pixel
+    fn png_pixel(png: &[u8], x: usize, y: usize) -> [u8; 4] {
+        let mut surface = cairo::ImageSurface::create_from_png(&mut &png[..]).unwrap();
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+        let [b, g, r, a] = data[y * stride + x * 4..][..4] else {
+            unreachable!()
+        };
+        [a, r, g, b]
+    }
 
     #[test]
     fn test_auto_sizes() {
@@ -70,4 +89,1002 @@ mod tests {
         let result = result.unwrap();
         assert!(result.get("body").is_some());
     }
+
+    #[test]
+    fn test_tokenizer_edge_cases() {
+        let css = r#"
+        /* status bar { width: 1px; } */
+        status bar {
+        content: "}";
+        width: 100px
+        }
+        "#;
+
+        let result = parse(css);
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].selector, "status bar");
+        assert_eq!(result[0].content.as_deref(), Some("}"));
+        assert_eq!(result[0].width, Some(100));
+
+        let result = render(css);
+        assert!(result.is_ok());
+        assert!(result.unwrap().contains_key("status bar"));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let css = r#"
+        body {
+        width: 100;
+        height: 100px;
+        background-color: idk;
+        colour: red;
+        }
+        "#;
+
+        let result = parse_with_diagnostics(css);
+        assert!(result.is_ok());
+
+        let (styles, diagnostics) = result.unwrap();
+        assert_eq!(styles[0].width, None);
+        assert_eq!(styles[0].height, Some(100));
+        assert_eq!(styles[0].background_color(), [0., 0., 0., 1.]);
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].property.as_deref(), Some("width"));
+        assert_eq!(diagnostics[0].value.as_deref(), Some("100"));
+        assert_eq!(diagnostics[0].span.start.line, 3);
+        assert_eq!(diagnostics[0].span.start.column, 9);
+        assert_eq!(diagnostics[0].span.end.column, 19);
+        assert_eq!(diagnostics[1].property.as_deref(), Some("background-color"));
+        assert_eq!(diagnostics[2].severity, Severity::Warning);
+        assert_eq!(diagnostics[2].property.as_deref(), Some("colour"));
+    }
+
+    #[test]
+    fn test_render_error() {
+        let mut styles = parse("battery { content: \"50%\"; }").unwrap();
+        styles[0].font.text_align = "justify".into();
+
+        let result = render(styles);
+        assert!(matches!(
+            &result,
+            Err(CssError::InvalidTextAlign { selector, value })
+                if selector == "battery" && value == "justify"
+        ));
+        assert_eq!(result.unwrap_err().selector(), "battery");
+    }
+
+    #[test]
+    fn test_unknown_unit() {
+        let css = "clock { width: 10px; } battery, .icon { height: 2QUX; }";
+
+        let result = render(css);
+        assert!(matches!(
+            &result,
+            Err(CssError::UnknownUnit { selector, unit })
+                if selector == "battery, .icon" && unit == "QUX"
+        ));
+        assert!(matches!(parse(css), Err(CssError::UnknownUnit { .. })));
+
+        // Diagnostics still cover the whole stylesheet
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert_eq!(styles.len(), 3);
+        assert_eq!(diagnostics.len(), 1);
+
+        // Elements only fail on the rules that apply to them
+        let stylesheet = Stylesheet::parse(css);
+        let clock = Element::parse("clock").unwrap();
+        assert!(render_element(&stylesheet, &clock, &RenderOptions::default()).is_ok());
+        let battery = Element::parse("battery").unwrap();
+        let result = render_element(&stylesheet, &battery, &RenderOptions::default());
+        assert!(matches!(result, Err(CssError::UnknownUnit { .. })));
+    }
+
+    #[test]
+    fn test_selector_lists() {
+        let css = r#"
+        one, two { width: 10px; height: 10px; background-color: blue; }
+        one { background-color: red; }
+        two { width: 20px; }
+        one { background-color: white; }
+        "#;
+
+        let result = parse(css);
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].selector, "one");
+        assert_eq!(result[0].width, Some(10));
+        assert_eq!(result[0].background_color(), [1., 1., 1., 1.]);
+        assert_eq!(result[1].selector, "two");
+        assert_eq!(result[1].width, Some(20));
+        assert_eq!(result[1].height, Some(10));
+        assert_eq!(result[1].background_color(), [0., 0., 1., 1.]);
+
+        let result = render(css);
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(result.contains_key("one"));
+        assert!(result.contains_key("two"));
+    }
+
+    #[test]
+    fn test_cascade() {
+        let css = r#"
+        #battery.icon { background-color: red; padding: 2px; }
+        .icon { width: 16px; height: 16px !important; background-color: blue; padding-left: 4px; }
+        #battery.icon { height: 32px; }
+        * { background-color: white; }
+        "#;
+
+        let result = parse(css);
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        let battery = result.iter().find(|s| s.selector == "#battery.icon");
+        assert!(battery.is_some());
+
+        let battery = battery.unwrap();
+        assert_eq!(battery.width, Some(16));
+        assert_eq!(battery.height, Some(16));
+        assert_eq!(battery.background_color(), [1., 0., 0., 1.]);
+        assert_eq!(battery.padding, [2; 4]);
+
+        let icon = result.iter().find(|s| s.selector == ".icon").unwrap();
+        assert_eq!(icon.background_color(), [0., 0., 1., 1.]);
+        assert_eq!(icon.padding, [0, 0, 0, 4]);
+    }
+
+    #[test]
+    fn test_render_element() {
+        let stylesheet = Stylesheet::parse(
+            r#"
+            .icon { width: 16px; height: 16px; background-color: white; }
+            battery.charging { width: 32px; }
+            battery.charging.low { height: 8px; }
+            #clock { width: 64px; }
+            "#,
+        );
+
+        let element = Element {
+            tag: Some("battery".into()),
+            classes: vec!["icon".into(), "charging".into()],
+            ..Default::default()
+        };
+        let result = render_element(&stylesheet, &element, &RenderOptions::default());
+        assert!(result.is_ok());
+        assert_eq!(png_size(&result.unwrap()), (32, 16));
+
+        let element = Element::parse("battery.icon.charging.low").unwrap();
+        let result = render_element(&stylesheet, &element, &RenderOptions::default());
+        assert_eq!(png_size(&result.unwrap()), (32, 8));
+
+        let element = Element::parse("#clock.icon").unwrap();
+        let result = render_element(&stylesheet, &element, &RenderOptions::default());
+        assert_eq!(png_size(&result.unwrap()), (64, 16));
+    }
+
+    #[test]
+    fn test_variables() {
+        let css = r#"
+        :root { --accent: #ff0000; --size: 10px; }
+        .icon { --size: 16px; }
+        #battery.icon { width: var(--size); height: var(--size); background-color: var(--accent); }
+        #clock { width: var(--size); background-color: var(--missing, white); }
+        "#;
+
+        let result = parse(css);
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        let battery = result
+            .iter()
+            .find(|s| s.selector == "#battery.icon")
+            .unwrap();
+        assert_eq!(battery.width, Some(16));
+        assert_eq!(battery.height, Some(16));
+        assert_eq!(battery.background_color(), [1., 0., 0., 1.]);
+
+        let clock = result.iter().find(|s| s.selector == "#clock").unwrap();
+        assert_eq!(clock.width, Some(10));
+        assert_eq!(clock.background_color(), [1., 1., 1., 1.]);
+
+        let options = RenderOptions {
+            variables: [("--size".to_string(), "32px".to_string())].into(),
+            ..Default::default()
+        };
+        let stylesheet = Stylesheet::parse(css);
+        let element = Element::parse("#clock").unwrap();
+        let result = render_element(&stylesheet, &element, &options);
+        assert_eq!(png_size(&result.unwrap()), (32, 5));
+
+        let result = css_image::render_with_options(css, &options);
+        assert!(result.is_ok());
+        assert_eq!(png_size(&result.unwrap()["#battery.icon"]), (32, 32));
+    }
+
+    #[test]
+    fn test_variables_in_shorthands() {
+        let css = r#"
+        :root { --p: 2px 4px; --b: 3px solid red; }
+        .padded { padding: var(--p); padding-left: 1px; }
+        .bordered { width: 4px; height: 4px; border: var(--b); background-color: white; }
+        .overridden { padding-top: 7px; padding: var(--p); border: var(--b); border-top-width: 5px; }
+        .invalid { --p: 1px 2px 3px 4px 5px; padding: var(--p); border: var(--missing); }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        let style = |selector: &str| styles.iter().find(|s| s.selector == selector).unwrap();
+
+        assert_eq!(style(".padded").padding, [2, 4, 2, 1]);
+
+        let bordered = &style(".bordered").border;
+        assert_eq!(bordered.width, [3; 4]);
+        assert_eq!(bordered.color, [[1., 0., 0., 1.]; 4]);
+
+        let overridden = style(".overridden");
+        assert_eq!(overridden.padding, [2, 4, 2, 4]);
+        assert_eq!(overridden.border.width, [5, 3, 3, 3]);
+
+        // Shorthands that are invalid once substituted leave their longhands unset
+        let invalid = style(".invalid");
+        assert_eq!(invalid.padding, [0; 4]);
+        assert_eq!(invalid.border.width, [0; 4]);
+
+        let bordered = &render(css).unwrap()[".bordered"];
+        assert_eq!(png_size(bordered), (10, 10));
+        assert_eq!(png_pixel(bordered, 1, 5), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(bordered, 5, 5), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_length_units() {
+        let css = r#"
+        #em { font-size: 1.5em; width: 10em; height: 1in; padding: 0.5em 1rem; margin: 12pt 1pc; }
+        #percent { width: 50%; height: 25%; margin-left: 10%; }
+        #viewport { width: 10vw; height: 50vh; }
+        "#;
+
+        let result = parse(css);
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        let em = result.iter().find(|s| s.selector == "#em").unwrap();
+        assert_eq!(em.font.size, 18.);
+        assert_eq!(em.width, Some(180));
+        assert_eq!(em.height, Some(96));
+        assert_eq!(em.padding, [9, 18, 9, 18]);
+        assert_eq!(em.margin, [16, 16, 16, 16]);
+
+        // Nothing to resolve percentages and viewport units against
+        let percent = result.iter().find(|s| s.selector == "#percent").unwrap();
+        assert_eq!(percent.width, None);
+        assert_eq!(percent.margin, [0; 4]);
+
+        let options = RenderOptions {
+            viewport: Some((1920., 1080.)),
+            containing_block: Some((200., 100.)),
+            ..Default::default()
+        };
+        let result = css_image::render_with_options(css, &options);
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        assert_eq!(png_size(&result["#percent"]), (120, 25));
+        assert_eq!(png_size(&result["#viewport"]), (192, 540));
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "body { width: -1em; height: 10foo; margin: -5%; letter-spacing: 5%; font-size: 2vw; }",
+        )
+        .unwrap();
+        let messages = diagnostics
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "Expected a non-negative length",
+                "Expected a length or percentage",
+                "Expected a length",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_math_functions() {
+        let css = r#"
+        #calc {
+            font-size: calc(1em + 4px);
+            width: calc(100% - 2em);
+            height: clamp(10px, 5vw, 40px);
+            padding: calc(1px + 1px) max(1em, 20px);
+            margin: min(-2px, 0px) 0;
+            border-radius: calc(1px - 5px);
+        }
+        "#;
+
+        let options = RenderOptions {
+            viewport: Some((400., 300.)),
+            containing_block: Some((200., 100.)),
+            ..Default::default()
+        };
+        let result = css_image::style::Parseable::parse_with_options(css, &options);
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        assert_eq!(result[0].font.size, 16.);
+        assert_eq!(result[0].width, Some(168));
+        assert_eq!(result[0].height, Some(20));
+        assert_eq!(result[0].padding, [2, 20, 2, 20]);
+        assert_eq!(result[0].margin, [-2, 0, -2, 0]);
+        assert_eq!(result[0].border.radii(10., 10.)[0], [0., 0.]);
+
+        let (_, diagnostics) =
+            parse_with_diagnostics("body { width: calc(1px + 2); height: calc(1px -2px); }")
+                .unwrap();
+        assert_eq!(diagnostics.len(), 2);
+
+        // Radii mixing lengths and percentages resolve against the border box
+        let (styles, diagnostics) =
+            parse_with_diagnostics("body { border-radius: min(5%, 2px) calc(50% - 4px); }")
+                .unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(styles[0].border.radii(20., 20.)[0], [1., 1.]);
+        assert_eq!(styles[0].border.radii(100., 100.)[1], [46., 46.]);
+    }
+
+    #[test]
+    fn test_derived_colors() {
+        let css = r#"
+        :root { --accent: #0000ff; }
+        .hover { background-color: color-mix(in srgb, var(--accent) 50%, white); }
+        .disabled { background-color: rgb(from var(--accent) r g b / 50%); }
+        .current { color: red; background-color: currentColor; }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+
+        let style = |selector| styles.iter().find(|s| s.selector == selector).unwrap();
+        assert_eq!(style(".hover").background_color(), [0.5, 0.5, 1., 1.]);
+        assert_eq!(style(".disabled").background_color(), [0., 0., 1., 0.5]);
+        assert_eq!(style(".current").background_color(), [1., 0., 0., 1.]);
+    }
+
+    #[test]
+    fn test_borders() {
+        let css = r#"
+        .box {
+            width: 10px;
+            height: 10px;
+            padding: 2px;
+            margin: 1px;
+            background-color: white;
+            border: 3px solid red;
+            border-left: 5px dashed blue;
+            border-bottom-style: none;
+        }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(styles[0].border.width, [3, 3, 0, 5]);
+        assert_eq!(
+            styles[0].border.style[3],
+            css_image::style::BorderStyle::Dashed
+        );
+
+        let result = render(css).unwrap();
+        let png = &result[".box"];
+        assert_eq!(
+            png_size(png),
+            (1 + 5 + 2 + 10 + 2 + 3 + 1, 1 + 3 + 2 + 10 + 2 + 1)
+        );
+        assert_eq!(png_pixel(png, 0, 0), [0, 0, 0, 0]);
+        assert_eq!(png_pixel(png, 10, 2), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(png, 10, 10), [255, 255, 255, 255]);
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "body { border: 1px 2px; border-style: wavy; border-width: 10%; border-top: red; }",
+        )
+        .unwrap();
+        assert_eq!(diagnostics.len(), 3);
+    }
+
+    #[test]
+    fn test_border_radius() {
+        let css = r#"
+        .pill { width: 40px; height: 20px; background-color: red; border-radius: 9999px; }
+        .corner { width: 20px; height: 20px; background-color: red; border-top-left-radius: 100% 50%; }
+        .ring { width: 10px; height: 10px; background-color: white; border: 5px solid blue; border-radius: 50%; }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        let pill = styles.iter().find(|s| s.selector == ".pill").unwrap();
+        assert_eq!(pill.border.radii(40., 20.), [[10., 10.]; 4]);
+
+        let result = render(css).unwrap();
+        let pill = &result[".pill"];
+        assert_eq!(png_pixel(pill, 0, 0), [0, 0, 0, 0]);
+        assert_eq!(png_pixel(pill, 1, 10), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(pill, 20, 0), [255, 255, 0, 0]);
+
+        let corner = &result[".corner"];
+        assert_eq!(png_pixel(corner, 1, 1), [0, 0, 0, 0]);
+        assert_eq!(png_pixel(corner, 5, 1), [0, 0, 0, 0]);
+        assert_eq!(png_pixel(corner, 1, 12), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(corner, 19, 1), [255, 255, 0, 0]);
+
+        let ring = &result[".ring"];
+        assert_eq!(png_pixel(ring, 1, 1), [0, 0, 0, 0]);
+        assert_eq!(png_pixel(ring, 10, 1), [255, 0, 0, 255]);
+        assert_eq!(png_pixel(ring, 10, 10), [255, 255, 255, 255]);
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "body { border-radius: 1px 2px 3px 4px 5px; border-top-left-radius: -1px; border-top-right-radius: 1px 2px 3px; }",
+        )
+        .unwrap();
+        assert_eq!(diagnostics.len(), 3);
+    }
+
+    #[test]
+    fn test_box_shadow() {
+        let css = r#"
+        .drop { width: 10px; height: 10px; background-color: white; box-shadow: 4px 2px blue, -2px 0 0 1px red; }
+        .margin { width: 10px; height: 10px; margin: 10px; background-color: white; box-shadow: 4px 2px blue; }
+        .blur { width: 10px; height: 10px; background-color: white; box-shadow: 0 0 4px black; }
+        .inset { width: 10px; height: 10px; background-color: white; box-shadow: inset 3px 0 blue; }
+        "#;
+
+        let result = render(css).unwrap();
+        // The spread red shadow reaches 3px left and 1px up and down, the blue one 4px right
+        // and 2px down
+        let drop = &result[".drop"];
+        assert_eq!(png_size(drop), (17, 13));
+        assert_eq!(png_pixel(drop, 0, 0), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(drop, 16, 12), [255, 0, 0, 255]);
+        assert_eq!(png_pixel(drop, 16, 0), [0, 0, 0, 0]);
+        assert_eq!(png_pixel(drop, 5, 5), [255, 255, 255, 255]);
+
+        assert_eq!(png_size(&result[".margin"]), (30, 30));
+        assert_eq!(png_size(&result[".blur"]), (22, 22));
+        let blur = &result[".blur"];
+        assert_eq!(png_pixel(blur, 0, 0), [0, 0, 0, 0]);
+        assert!((1..255).contains(&png_pixel(blur, 4, 11)[0]));
+
+        let inset = &result[".inset"];
+        assert_eq!(png_size(inset), (10, 10));
+        assert_eq!(png_pixel(inset, 1, 5), [255, 0, 0, 255]);
+        assert_eq!(png_pixel(inset, 5, 5), [255, 255, 255, 255]);
+
+        let options = RenderOptions {
+            clip_shadows: true,
+            ..Default::default()
+        };
+        let result = css_image::render_with_options(css, &options).unwrap();
+        assert_eq!(png_size(&result[".drop"]), (10, 10));
+        assert_eq!(png_pixel(&result[".margin"], 23, 21), [255, 0, 0, 255]);
+
+        let (_, diagnostics) =
+            parse_with_diagnostics("body { box-shadow: 1px; } a { box-shadow: 0 0 -1px red; }")
+                .unwrap();
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_gradients() {
+        let css = r#"
+        .linear { width: 100px; height: 10px; background: linear-gradient(to right, red 50%, blue 50%); }
+        .radial { width: 20px; height: 20px; background: radial-gradient(circle closest-side, lime 50%, transparent 50%) blue; }
+        .conic { width: 20px; height: 20px; background-image: conic-gradient(red 25%, lime 0 50%, blue 0); }
+        .clip { width: 20px; height: 20px; padding: 5px; background: linear-gradient(red, red); background-clip: content-box; }
+        .text { font-size: 40px; background: linear-gradient(red, red); background-clip: text; color: transparent; content: "Hi"; }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        let linear = styles.iter().find(|s| s.selector == ".linear").unwrap();
+        assert_eq!(linear.background_color(), [0., 0., 0., 0.]);
+        assert_eq!(linear.background.len(), 2);
+
+        let result = render(css).unwrap();
+        let linear = &result[".linear"];
+        assert_eq!(png_pixel(linear, 10, 5), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(linear, 90, 5), [255, 0, 0, 255]);
+
+        let radial = &result[".radial"];
+        assert_eq!(png_pixel(radial, 10, 10), [255, 0, 255, 0]);
+        assert_eq!(png_pixel(radial, 1, 1), [255, 0, 0, 255]);
+
+        let conic = &result[".conic"];
+        assert_eq!(png_pixel(conic, 15, 5), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(conic, 15, 15), [255, 0, 255, 0]);
+        assert_eq!(png_pixel(conic, 5, 15), [255, 0, 0, 255]);
+
+        let clip = &result[".clip"];
+        assert_eq!(png_pixel(clip, 2, 2), [0, 0, 0, 0]);
+        assert_eq!(png_pixel(clip, 15, 15), [255, 255, 0, 0]);
+
+        // Only the glyphs are painted
+        let text = &result[".text"];
+        let (width, height) = png_size(text);
+        let pixels = (0..width as usize)
+            .flat_map(|x| (0..height as usize).map(move |y| png_pixel(text, x, y)))
+            .collect::<Vec<_>>();
+        assert!(pixels.contains(&[255, 255, 0, 0]));
+        assert!(pixels.contains(&[0, 0, 0, 0]));
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "a { background-image: linear-gradient(red); } b { background-clip: foo; } c { background: red blue; }",
+        )
+        .unwrap();
+        assert_eq!(diagnostics.len(), 3);
+    }
+
+    #[test]
+    fn test_background_images() {
+        // A 2x2 red icon to composite
+        let icon = render(".icon { width: 2px; height: 2px; background-color: red; }").unwrap()
+            [".icon"]
+            .clone();
+        let css = r#"
+        .corner { width: 20px; height: 20px; background: url(icon.png) blue; background-size: 10px; background-position: right 2px bottom 0; background-repeat: no-repeat; }
+        .tiled { width: 8px; height: 8px; background-image: url("icon.png"); background-size: 3px; background-repeat: space; background-color: blue; }
+        .cover { width: 10px; height: 4px; padding: 2px; border: 2px solid blue; background-image: url(icon.png); background-size: cover; background-repeat: no-repeat; background-origin: content-box; background-clip: padding-box; }
+        .missing { width: 4px; height: 4px; background: url(missing.png) blue; }
+        "#;
+
+        let options = RenderOptions {
+            resolver: Some(Resolver::new(move |url| {
+                (url == "icon.png").then(|| icon.clone())
+            })),
+            ..Default::default()
+        };
+        let result = css_image::render_with_options(css, &options).unwrap();
+
+        let corner = &result[".corner"];
+        assert_eq!(png_pixel(corner, 12, 15), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(corner, 17, 15), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(corner, 19, 15), [255, 0, 0, 255]);
+        assert_eq!(png_pixel(corner, 12, 5), [255, 0, 0, 255]);
+
+        // Two tiles each way, spaced 2px apart
+        let tiled = &result[".tiled"];
+        assert_eq!(png_pixel(tiled, 0, 0), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(tiled, 3, 0), [255, 0, 0, 255]);
+        assert_eq!(png_pixel(tiled, 4, 4), [255, 0, 0, 255]);
+        assert_eq!(png_pixel(tiled, 6, 6), [255, 255, 0, 0]);
+
+        // Covers the content box, clipped to the padding box over the default black background
+        let cover = &result[".cover"];
+        assert_eq!(png_pixel(cover, 2, 2), [255, 0, 0, 0]);
+        assert_eq!(png_pixel(cover, 4, 4), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(cover, 5, 9), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(cover, 1, 5), [255, 0, 0, 255]);
+
+        assert_eq!(png_pixel(&result[".missing"], 1, 1), [255, 0, 0, 255]);
+
+        // Without a resolver urls are read from the base directory
+        let dir = std::env::temp_dir().join("css-image-background-images");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("icon.png"),
+            options.resolver.unwrap().resolve("icon.png").unwrap(),
+        )
+        .unwrap();
+        let options = RenderOptions {
+            base_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let result = css_image::render_with_options(css, &options).unwrap();
+        assert_eq!(png_pixel(&result[".corner"], 12, 15), [255, 255, 0, 0]);
+
+        // Urls can't reach files outside of the base directory
+        let base_dir = dir.join("base");
+        std::fs::create_dir_all(&base_dir).unwrap();
+        let css = format!(
+            ".parent {{ width: 4px; height: 4px; background: url(../icon.png), blue; }} \
+             .absolute {{ width: 4px; height: 4px; background: url(\"{}\"), blue; }}",
+            dir.join("icon.png").display()
+        );
+        let options = RenderOptions {
+            base_dir: Some(base_dir),
+            ..Default::default()
+        };
+        let result = css_image::render_with_options(css.as_str(), &options).unwrap();
+        assert_eq!(png_pixel(&result[".parent"], 1, 1), [255, 0, 0, 255]);
+        assert_eq!(png_pixel(&result[".absolute"], 1, 1), [255, 0, 0, 255]);
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "a { background-size: 1px 2px 3px; background-repeat: repeat-z; background-position: left right; background-origin: text; }",
+        )
+        .unwrap();
+        assert_eq!(diagnostics.len(), 4);
+    }
+
+    #[test]
+    fn test_background_layers() {
+        let css = r#"
+        .layers { width: 20px; height: 10px; background: linear-gradient(to right, red 50%, transparent 50%), linear-gradient(lime, lime) right top / 50% 50% no-repeat, blue; }
+        .longhands { width: 10px; height: 10px; padding: 2px; background-image: linear-gradient(red, red), linear-gradient(lime, lime); background-clip: content-box, border-box; background-color: blue; }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(styles[0].background.len(), 3);
+        assert_eq!(styles[0].background_color(), [0., 0., 1., 1.]);
+
+        let result = render(css).unwrap();
+        let layers = &result[".layers"];
+        assert_eq!(png_pixel(layers, 5, 5), [255, 255, 0, 0]);
+        assert_eq!(png_pixel(layers, 15, 2), [255, 0, 255, 0]);
+        assert_eq!(png_pixel(layers, 15, 8), [255, 0, 0, 255]);
+
+        // The color is clipped like the last image
+        let longhands = &result[".longhands"];
+        assert_eq!(png_pixel(longhands, 1, 1), [255, 0, 255, 0]);
+        assert_eq!(png_pixel(longhands, 7, 7), [255, 255, 0, 0]);
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "a { background-image: url(a.png), foo; } b { background: red, url(a.png); } c { background-size: cover, ; }",
+        )
+        .unwrap();
+        assert_eq!(diagnostics.len(), 3);
+    }
+
+    #[test]
+    fn test_text_wrapping() {
+        let css = r#"
+        .wrap { width: 30px; font-size: 10px; line-height: 20px; content: "aaa aaa aaa"; color: red; background-color: white; }
+        .pre { font-size: 10px; line-height: 2; white-space: pre; content: "a\A b\A\A c"; }
+        .nowrap { width: 30px; font-size: 10px; line-height: 10px; white-space: nowrap; content: "aaa aaa"; }
+        .collapsed { font-size: 10px; line-height: 10px; content: "a\A b"; }
+        "#;
+
+        let (_, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+
+        let result = render(css).unwrap();
+        let wrap = &result[".wrap"];
+        assert_eq!(png_size(wrap), (30, 60));
+        // Every line box has some of the text in it
+        for line in 0..3 {
+            let painted = (0..30).any(|x| {
+                (line * 20..line * 20 + 20).any(|y| png_pixel(wrap, x, y) != [255, 255, 255, 255])
+            });
+            assert!(painted);
+        }
+
+        assert_eq!(png_size(&result[".pre"]).1, 80);
+        assert_eq!(png_size(&result[".nowrap"]), (30, 10));
+        assert_eq!(png_size(&result[".collapsed"]).1, 10);
+
+        let (_, diagnostics) =
+            parse_with_diagnostics("a { white-space: wrap; line-height: -2; }").unwrap();
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_bidi() {
+        let css = r#"
+        .rtl { width: 100px; font-size: 10px; direction: rtl; content: "abc"; color: red; background-color: white; }
+        .override { font-size: 10px; direction: rtl; unicode-bidi: bidi-override; content: "abc"; }
+        .reversed { font-size: 10px; content: "cba"; }
+        .hebrew { font-size: 10px; font-style: italic; content: "abc אבג"; }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        let hebrew = styles.iter().find(|s| s.selector == ".hebrew").unwrap();
+        assert_eq!(hebrew.font.description.family().as_deref(), Some("Arial"));
+        assert_eq!(hebrew.font.description.style(), pango::Style::Italic);
+
+        let result = render(css).unwrap();
+
+        // `start` is on the right for right to left content
+        let rtl = &result[".rtl"];
+        let (width, height) = png_size(rtl);
+        let painted = |columns: std::ops::Range<usize>| {
+            columns
+                .into_iter()
+                .any(|x| (0..height as usize).any(|y| png_pixel(rtl, x, y) != [255, 255, 255, 255]))
+        };
+        assert!(!painted(0..50));
+        assert!(painted(50..width as usize));
+
+        assert_eq!(result[".override"], result[".reversed"]);
+        assert!(png_size(&result[".hebrew"]).0 > 0);
+
+        let (_, diagnostics) =
+            parse_with_diagnostics("a { direction: up; unicode-bidi: reverse; }").unwrap();
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_spacing() {
+        let css = r#"
+        .plain { font-size: 10px; content: "aa aa"; }
+        .spaced { font-size: 10px; letter-spacing: 1px; word-spacing: 5px; content: "aa aa"; }
+        .normal { font-size: 10px; letter-spacing: normal; word-spacing: normal; content: "aa aa"; }
+        "#;
+
+        let (_, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+
+        // The auto width makes room for the spacing
+        let result = render(css).unwrap();
+        let plain = png_size(&result[".plain"]).0;
+        assert_eq!(png_size(&result[".spaced"]).0, plain + 4 + 5);
+        assert_eq!(result[".normal"], result[".plain"]);
+
+        let (_, diagnostics) =
+            parse_with_diagnostics("a { word-spacing: 5%; letter-spacing: wide; }").unwrap();
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_font_face() {
+        // Fixture Mono only has glyphs for spaces, "i" and "M"
+        let css = r#"
+        @font-face { font-family: Brand; src: url(missing.woff2) format("woff2"), url(Mono.ttf); }
+        @font-face { font-family: "Brand"; src: url("MonoBold.ttf") format("truetype"); font-weight: 600 900; }
+        @font-face { font-family: Missing; src: url(missing.ttf); }
+        @font-face { font-family: Swapped; src: url(MonoBold.ttf); }
+        @font-face { font-family: Swapped; src: url(Mono.ttf); font-weight: bold; }
+        .brand { font-size: 10px; font-family: Brand; content: "iiii"; color: white; }
+        .wide { font-size: 10px; font-family: Brand; content: "MMMM"; color: white; }
+        .bold { font-size: 10px; font-family: Brand; font-weight: bold; content: "iiii"; color: white; }
+        .missing { font-size: 10px; font-family: Missing, Brand; content: "iiii"; color: white; }
+        .swapped { font-size: 10px; font-family: Swapped; content: "iiii"; color: white; }
+        .swapped-bold { font-size: 10px; font-family: Swapped; font-weight: bold; content: "iiii"; color: white; }
+        "#;
+        let (_, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+
+        let options = RenderOptions {
+            base_dir: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures").into()),
+            ..Default::default()
+        };
+        let result = css_image::render_with_options(css, &options).unwrap();
+        // Every glyph is 1233/2048 em wide
+        assert_eq!(png_size(&result[".brand"]).0, 24);
+        assert_eq!(png_size(&result[".wide"]).0, 24);
+        assert_eq!(png_size(&result[".bold"]), png_size(&result[".brand"]));
+        assert_ne!(result[".bold"], result[".brand"]);
+        // Faces that fail to load fall back to the next family
+        assert_eq!(result[".missing"], result[".brand"]);
+        // Faces are matched by the descriptors their rules declare, not by what their files say
+        assert_eq!(result[".swapped"], result[".bold"]);
+        assert_eq!(result[".swapped-bold"], result[".brand"]);
+
+        // Loaded faces are reported under the family their rule declares
+        let rendered = css_image::render_with_metadata(css, &options).unwrap();
+        assert_eq!(rendered[".brand"].font_families, ["Brand"]);
+        assert_eq!(rendered[".missing"].font_families, ["Brand"]);
+        assert_eq!(rendered[".swapped"].font_families, ["Swapped"]);
+        assert_eq!(rendered[".bold"].png, result[".bold"]);
+
+        // Font urls can't reach files outside of the base directory
+        let dir = std::env::temp_dir().join("css-image-font-face");
+        std::fs::create_dir_all(dir.join("base")).unwrap();
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/Mono.ttf");
+        std::fs::copy(fixture, dir.join("Mono.ttf")).unwrap();
+        let css = format!(
+            "@font-face {{ font-family: Parent; src: url(../Mono.ttf); }} \
+             @font-face {{ font-family: Absolute; src: url(\"{fixture}\"); }} \
+             .parent {{ font-family: Parent; content: \"iiii\"; }} \
+             .absolute {{ font-family: Absolute; content: \"iiii\"; }}"
+        );
+        let options = RenderOptions {
+            base_dir: Some(dir.join("base")),
+            ..Default::default()
+        };
+        let escaped = css_image::render_with_metadata(css.as_str(), &options).unwrap();
+        assert!(!escaped[".parent"].font_families.contains(&"Parent".into()));
+        assert!(!escaped[".absolute"]
+            .font_families
+            .contains(&"Absolute".into()));
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "@font-face { font-family: A; } @font-face { font-family: B; src: a.ttf; font-display: swap; font-weight: 0; }",
+        )
+        .unwrap();
+        let messages = diagnostics
+            .iter()
+            .map(|d| (d.severity, d.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                (
+                    Severity::Error,
+                    "`@font-face` needs a font-family and a src"
+                ),
+                (
+                    Severity::Error,
+                    "Expected a list of `url()`s and `local()`s"
+                ),
+                (Severity::Warning, "Unknown descriptor"),
+                (
+                    Severity::Error,
+                    "Expected one or two weights between 1 and 1000"
+                ),
+                (
+                    Severity::Error,
+                    "`@font-face` needs a font-family and a src"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_font_families() {
+        let css = r#"
+        .list { font-size: 10px; font-family: "Missing Font", DejaVu  Sans Mono, serif; content: "iiii"; }
+        .mono { font-size: 10px; font-family: 'DejaVu Sans Mono'; content: "iiii"; }
+        .generic { font-family: System-UI; content: "a"; }
+        .quoted { font-family: "serif", monospace; content: "a"; }
+        .empty { font-family: serif; }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        let family = |selector: &str| {
+            let style = styles.iter().find(|s| s.selector == selector).unwrap();
+            style.font.description.family().unwrap().to_string()
+        };
+        assert_eq!(family(".list"), "Missing Font,DejaVu Sans Mono,serif");
+        assert_eq!(family(".generic"), "system-ui,sans-serif");
+        assert_eq!(family(".quoted"), "serif,monospace");
+
+        let rendered = css_image::render_with_metadata(css, &RenderOptions::default()).unwrap();
+        assert_eq!(rendered[".list"].font_families, ["DejaVu Sans Mono"]);
+        assert_eq!(rendered[".list"].png, rendered[".mono"].png);
+        assert_eq!(rendered[".generic"].font_families.len(), 1);
+        let Rendered { font_families, .. } = &rendered[".empty"];
+        assert!(font_families.is_empty());
+
+        let (_, diagnostics) =
+            parse_with_diagnostics("a { font-family: Inter, 12px; } b { font-family: ; }").unwrap();
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_font_weight() {
+        let css = r#"
+        .numeric { font-weight: 600; font-stretch: condensed; }
+        .bolder { font-weight: bolder; font-stretch: 80%; }
+        .lighter { font-weight: LIGHTER; font-variation-settings: "wght" 650, "wdth" 80, 'wght' 700; }
+        .normal { font-weight: normal; font-stretch: normal; font-variation-settings: normal; }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        let font = |selector: &str| &styles.iter().find(|s| s.selector == selector).unwrap().font;
+
+        let numeric = font(".numeric");
+        assert_eq!((numeric.weight.get(), numeric.stretch.get()), (600., 75.));
+        assert_eq!(numeric.description.weight(), pango::Weight::Semibold);
+        assert_eq!(numeric.description.stretch(), pango::Stretch::Condensed);
+
+        // The initial weight is what they are relative to
+        let bolder = font(".bolder");
+        assert_eq!((bolder.weight.get(), bolder.stretch.get()), (700., 80.));
+        assert_eq!(bolder.description.stretch(), pango::Stretch::Condensed);
+        assert_eq!(bolder.description.variations().as_deref(), Some("wdth=80"));
+
+        let lighter = font(".lighter");
+        assert_eq!(lighter.weight.get(), 100.);
+        assert_eq!(
+            lighter.variations,
+            [("wdth".to_string(), 80.), ("wght".to_string(), 700.)]
+        );
+        assert_eq!(
+            lighter.description.variations().as_deref(),
+            Some("wdth=80,wght=700")
+        );
+
+        let normal = font(".normal");
+        assert_eq!((normal.weight.get(), normal.stretch.get()), (400., 100.));
+        assert!(normal.variations.is_empty());
+        assert_eq!(normal.description.variations(), None);
+
+        use css_image::style::{FontStretch, FontWeight};
+        assert_eq!(FontWeight::new(0.), None);
+        assert_eq!(
+            FontWeight::new(1000.).map(FontWeight::to_pango),
+            Some(pango::Weight::Ultraheavy)
+        );
+        assert_eq!(FontStretch::new(-10.), None);
+        assert_eq!(
+            FontStretch::new(90.).map(FontStretch::to_pango),
+            Some(pango::Stretch::SemiCondensed)
+        );
+        // Pango keeps weights between the named ones
+        let mut description = pango::FontDescription::new();
+        description.set_weight(FontWeight::new(450.).unwrap().to_pango());
+        assert!(description.to_str().contains("weight=450"));
+
+        let text = |weight: &str| {
+            let css = format!(".a {{ font-size: 10px; font-family: DejaVu Sans; font-weight: {weight}; content: \"aaa\"; color: white; }}");
+            render(css.as_str()).unwrap().remove(".a").unwrap()
+        };
+        assert_eq!(text("700"), text("bold"));
+        assert_ne!(text("600"), text("400"));
+        assert_eq!(text("bolder"), text("bold"));
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "a { font-weight: 0; font-stretch: -10%; font-variation-settings: \"wg\" 1; } b { font-weight: 1001; font-stretch: wide; font-variation-settings: wght 1; }",
+        )
+        .unwrap();
+        assert_eq!(diagnostics.len(), 6);
+    }
+
+    #[test]
+    fn test_text_effects() {
+        let css = r#"
+        .plain { font-size: 20px; content: "aa"; color: white; background-color: white; }
+        .underline { font-size: 20px; content: "aa"; color: white; background-color: white; text-decoration: underline red 2px; }
+        .strike { font-size: 20px; content: "aa"; color: white; background-color: white; text-decoration-line: line-through; text-decoration-color: red; text-decoration-style: dashed; }
+        .stroke { font-size: 20px; content: "aa"; color: white; background-color: white; -webkit-text-stroke: 1px red; }
+        .shadow { font-size: 20px; content: "aa"; color: white; background-color: white; text-shadow: 2px 3px 2px red; }
+        .shadows { font-size: 20px; content: "aa"; text-shadow: 1px 1px red, -4px 0 blue; }
+        .fixed { width: 40px; font-size: 20px; content: "aa"; text-shadow: 2px 3px 2px red; }
+        "#;
+
+        let (styles, diagnostics) = parse_with_diagnostics(css).unwrap();
+        assert!(diagnostics.is_empty());
+        let strike = &styles
+            .iter()
+            .find(|s| s.selector == ".strike")
+            .unwrap()
+            .font;
+        assert!(strike.text_decoration.line_through && !strike.text_decoration.underline);
+        assert_eq!(strike.text_decoration.color, [1., 0., 0., 1.]);
+
+        let result = render(css).unwrap();
+        // Rows with red in them, antialiased over the white background
+        let red = |selector: &str| {
+            let png = &result[selector];
+            let (width, height) = png_size(png);
+            (0..width as usize)
+                .flat_map(|x| (0..height as usize).map(move |y| (x, y)))
+                .filter(|&(x, y)| {
+                    let [a, r, g, b] = png_pixel(png, x, y);
+                    a == 255 && r == 255 && g < 128 && b < 128
+                })
+                .map(|(_, y)| y)
+                .collect::<Vec<_>>()
+        };
+        assert!(red(".plain").is_empty());
+        // Underlines go under the baseline and line-throughs through the middle of the glyphs
+        let (underline, strike) = (red(".underline"), red(".strike"));
+        assert!(!underline.is_empty() && !strike.is_empty());
+        assert!(underline.iter().min() > strike.iter().max());
+        assert!(!red(".stroke").is_empty());
+        assert!(!red(".shadow").is_empty());
+
+        // The blur reaches 3px past the offset shadow, growing the auto sized image to fit it
+        let (width, height) = png_size(&result[".plain"]);
+        assert_eq!(png_size(&result[".shadow"]), (width + 6, height + 6));
+        assert_eq!(png_size(&result[".shadows"]), (width + 5, height + 1));
+        assert_eq!(png_size(&result[".fixed"]), (40, height + 6));
+
+        let (_, diagnostics) = parse_with_diagnostics(
+            "a { text-decoration: underline underline; text-decoration-style: zigzag; text-shadow: inset 1px 1px; } b { -webkit-text-stroke: 1px 2px; -webkit-text-stroke-width: 10%; text-decoration-line: blink; }",
+        )
+        .unwrap();
+        assert_eq!(diagnostics.len(), 6);
+    }
 }